@@ -5,13 +5,61 @@ extern crate base64;
 extern crate rusttype;
 
 
+mod raster;
+
+
+use std::str::FromStr;
+
 use base64::display::Base64Display;
-use rusttype::{Font, FontCollection, Scale, point, Point, PositionedGlyph};
+use rusttype::{Font, FontCollection, Scale, point, Point};
 
+use raster::{Canvas, parse_hex_color};
 
-const FONT_DATA: &'static [u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),
+
+const FONT_DATA: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),
                                                         "/DejaVuSans.ttf"));
 const FONT_SIZE: f32 = 11.;
+/// Horizontal padding around text, same as used by shields.io
+const TEXT_PADDING: u32 = 10;
+/// Largest supported scale factor for PNG rendering
+pub const MAX_PNG_SCALE: u32 = 4;
+
+
+include!(concat!(env!("OUT_DIR"), "/widths.rs"));
+
+
+/// Visual style of a badge, named after the styles offered by shields.io
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BadgeStyle {
+    Flat,
+    FlatSquare,
+    Plastic,
+    ForTheBadge,
+    Social,
+}
+
+
+impl Default for BadgeStyle {
+    fn default() -> BadgeStyle {
+        BadgeStyle::Flat
+    }
+}
+
+
+impl FromStr for BadgeStyle {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<BadgeStyle, String> {
+        match input {
+            "flat" => Ok(BadgeStyle::Flat),
+            "flat-square" => Ok(BadgeStyle::FlatSquare),
+            "plastic" => Ok(BadgeStyle::Plastic),
+            "for-the-badge" => Ok(BadgeStyle::ForTheBadge),
+            "social" => Ok(BadgeStyle::Social),
+            _ => Err(format!("unknown badge style: {}", input)),
+        }
+    }
+}
 
 
 pub struct BadgeOptions {
@@ -21,6 +69,12 @@ pub struct BadgeOptions {
     pub status: String,
     /// HTML color of badge
     pub color: String,
+    /// HTML color of the subject side of badge
+    pub subject_color: String,
+    /// Visual style of badge
+    pub style: BadgeStyle,
+    /// Longer description for assistive technology, in addition to subject and status
+    pub description: Option<String>,
 }
 
 
@@ -30,6 +84,9 @@ impl Default for BadgeOptions {
             subject: "build".to_owned(),
             status: "passing".to_owned(),
             color: "#4c1".to_owned(),
+            subject_color: "#555".to_owned(),
+            style: BadgeStyle::Flat,
+            description: None,
         }
     }
 }
@@ -42,16 +99,30 @@ struct BadgeStaticData {
 }
 
 
+/// Escapes text for use in SVG content and attribute values
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+
 lazy_static! {
     static ref DATA: BadgeStaticData = {
         let collection = FontCollection::from_bytes(FONT_DATA)
             .expect("failed to parse font collection");
         let font = collection.into_font()
             .expect("failed to load font data");
-        let scale = Scale {
-            x: FONT_SIZE,
-            y: FONT_SIZE,
-        };
+        let scale = em_scale(&font, FONT_SIZE);
         let v_metrics = font.v_metrics(scale);
         let offset = point(0.0, v_metrics.ascent);
 
@@ -60,6 +131,79 @@ lazy_static! {
 }
 
 
+/// Scale at which one em is `size` pixels, like a CSS font size.
+///
+/// A rusttype `Scale` is the height from descent to ascent instead, which
+/// is larger than the em box for most fonts.
+fn em_scale(font: &Font, size: f32) -> Scale {
+    let v_metrics = font.v_metrics_unscaled();
+    Scale::uniform(size * (v_metrics.ascent - v_metrics.descent) / font.units_per_em() as f32)
+}
+
+
+/// Advance width of a character, looked up in the precomputed table if possible
+fn char_width(c: char) -> f32 {
+    let code = c as u32;
+    if (32..127).contains(&code) {
+        ASCII_ADVANCE_WIDTHS[(code - 32) as usize]
+    } else {
+        let mut buf = [0; 4];
+        DATA.font.layout(c.encode_utf8(&mut buf), DATA.scale, DATA.offset)
+            .map(|g| g.unpositioned().h_metrics().advance_width)
+            .sum()
+    }
+}
+
+
+/// Geometry of a badge, shared by the SVG and PNG renderers
+struct Layout {
+    /// Subject and status as displayed, e.g. uppercased for `ForTheBadge`
+    subject: String,
+    status: String,
+    /// Width of the subject box
+    left_width: u32,
+    /// Horizontal offset and width of the status box
+    status_x: u32,
+    right_width: u32,
+    width: u32,
+    height: u32,
+    /// Baseline of the text
+    text_y: u32,
+    font_size: f32,
+    letter_spacing: f32,
+}
+
+
+/// Draws text with the given color and opacity, horizontally centered
+/// around `origin` in unscaled coordinates
+fn draw_text(canvas: &mut Canvas, text: &str, layout: &Layout, origin: (f32, f32), scale: u32,
+             paint: ([u8; 3], f32)) {
+    let (center_x, baseline) = origin;
+    let (color, alpha) = paint;
+    let s = scale as f32;
+    let size_ratio = layout.font_size / FONT_SIZE;
+    let glyph_scale = em_scale(&DATA.font, layout.font_size * s);
+
+    let text_width = text.chars()
+        .map(|c| char_width(c) * size_ratio + layout.letter_spacing)
+        .sum::<f32>();
+    let mut x = (center_x - text_width / 2.) * s;
+
+    for c in text.chars() {
+        let mut buf = [0; 4];
+        for glyph in DATA.font.layout(c.encode_utf8(&mut buf), glyph_scale, point(x, baseline * s)) {
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                glyph.draw(|gx, gy, coverage| {
+                    canvas.blend(bounding_box.min.x + gx as i32, bounding_box.min.y + gy as i32,
+                                 color, coverage * alpha);
+                });
+            }
+        }
+        x += (char_width(c) * size_ratio + layout.letter_spacing) * s;
+    }
+}
+
+
 pub struct Badge {
     options: BadgeOptions
 }
@@ -77,11 +221,129 @@ impl Badge {
     }
 
 
+    /// Renders the badge as PNG, with all dimensions multiplied by `scale`
+    /// (clamped to `1..=MAX_PNG_SCALE`) for HiDPI displays.
+    ///
+    /// The layout is the same as the one of the SVG, but gradients are left out.
+    pub fn to_png(&self, scale: u32) -> Vec<u8> {
+        let scale = scale.max(1).min(MAX_PNG_SCALE);
+        let layout = self.layout();
+        let s = scale as f32;
+
+        let mut canvas = Canvas::new(layout.width * scale, layout.height * scale);
+
+        let subject_color = parse_hex_color(&self.options.subject_color).unwrap_or([0x55, 0x55, 0x55]);
+        let color = parse_hex_color(&self.options.color).unwrap_or([0x9f, 0x9f, 0x9f]);
+        let left_x1 = layout.left_width as f32 * s;
+        let status_x0 = layout.status_x as f32 * s;
+        let status_x1 = (layout.status_x + layout.right_width) as f32 * s;
+        let height = layout.height as f32 * s;
+
+        let (text_color, shadow) = match self.options.style {
+            BadgeStyle::Flat | BadgeStyle::Plastic => ([0xff, 0xff, 0xff], Some(([0x01, 0x01, 0x01], 0.3))),
+            BadgeStyle::FlatSquare | BadgeStyle::ForTheBadge => ([0xff, 0xff, 0xff], None),
+            BadgeStyle::Social => ([0x33, 0x33, 0x33], Some(([0xff, 0xff, 0xff], 1.))),
+        };
+
+        match self.options.style {
+            BadgeStyle::Social => {
+                let border = [0xd5, 0xd5, 0xd5];
+                let (radius, width) = (2. * s, layout.width as f32 * s);
+                let bubble_width = status_x1 - status_x0;
+                canvas.fill_rounded_rect((0., 0., left_x1, height), radius, (0., width), border);
+                canvas.fill_rounded_rect((s, s, left_x1 - 2. * s, height - 2. * s), radius, (0., width),
+                                         [0xfc, 0xfc, 0xfc]);
+                canvas.fill_rounded_rect((status_x0, 0., bubble_width, height), radius, (0., width), border);
+                canvas.fill_rounded_rect((status_x0 + s, s, bubble_width - 2. * s, height - 2. * s), radius,
+                                         (0., width), [0xfa, 0xfa, 0xfa]);
+            },
+            style => {
+                let radius = match style {
+                    BadgeStyle::Flat => 3.,
+                    BadgeStyle::Plastic => 4.,
+                    _ => 0.,
+                } * s;
+                canvas.fill_rounded_rect((0., 0., status_x1, height), radius, (0., left_x1), subject_color);
+                canvas.fill_rounded_rect((0., 0., status_x1, height), radius, (status_x0, status_x1), color);
+            },
+        }
+
+        let texts = [
+            (&layout.subject, layout.left_width as f32 / 2.),
+            (&layout.status, layout.status_x as f32 + layout.right_width as f32 / 2.),
+        ];
+        for &(text, center_x) in texts.iter() {
+            let text_y = layout.text_y as f32;
+            if let Some(shadow_paint) = shadow {
+                draw_text(&mut canvas, text, &layout, (center_x, text_y + 1.), scale, shadow_paint);
+            }
+            draw_text(&mut canvas, text, &layout, (center_x, text_y), scale, (text_color, 1.));
+        }
+
+        canvas.to_png()
+    }
+
+
+    fn layout(&self) -> Layout {
+        match self.options.style {
+            BadgeStyle::ForTheBadge => {
+                let subject = self.options.subject.to_uppercase();
+                let status = self.options.status.to_uppercase();
+                let left_width = self.calculate_spaced_width(&subject) + 20;
+                let right_width = self.calculate_spaced_width(&status) + 20;
+
+                Layout {
+                    subject, status, left_width, right_width,
+                    status_x: left_width,
+                    width: left_width + right_width,
+                    height: 28,
+                    text_y: 18,
+                    font_size: 10.,
+                    letter_spacing: 1.,
+                }
+            },
+            style => {
+                let left_width = self.calculate_width(&self.options.subject) + TEXT_PADDING;
+                let right_width = self.calculate_width(&self.options.status) + TEXT_PADDING;
+                // the social status is rendered in a speech bubble, pointing at the subject
+                let (status_x, width) = match style {
+                    BadgeStyle::Social => (left_width + 6, left_width + 6 + right_width + 1),
+                    _ => (left_width, left_width + right_width),
+                };
+                let (height, text_y) = match style {
+                    BadgeStyle::Plastic => (18, 13),
+                    _ => (20, 14),
+                };
+
+                Layout {
+                    subject: self.options.subject.clone(),
+                    status: self.options.status.clone(),
+                    left_width, status_x, right_width, width, height, text_y,
+                    font_size: FONT_SIZE,
+                    letter_spacing: 0.,
+                }
+            },
+        }
+    }
+
+
     pub fn to_svg(&self) -> String {
-        let left_width = self.calculate_width(&self.options.subject) + 6;
-        let right_width = self.calculate_width(&self.options.status) + 6;
+        match self.options.style {
+            BadgeStyle::Flat => self.to_flat_svg(),
+            BadgeStyle::FlatSquare => self.to_flat_square_svg(),
+            BadgeStyle::Plastic => self.to_plastic_svg(),
+            BadgeStyle::ForTheBadge => self.to_for_the_badge_svg(),
+            BadgeStyle::Social => self.to_social_svg(),
+        }
+    }
+
+
+    fn to_flat_svg(&self) -> String {
+        let Layout { left_width, right_width, .. } = self.layout();
+
+        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="20" {accessibility_attributes}>
+  {accessibility_elements}
 
-        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="20">
   <linearGradient id="smooth" x2="0" y2="100%">
     <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
     <stop offset="1" stop-opacity=".1"/>
@@ -92,7 +354,7 @@ impl Badge {
   </mask>
 
   <g mask="url(#round)">
-    <rect width="{}" height="20" fill="#555"/>
+    <rect width="{}" height="20" fill="{}"/>
     <rect x="{}" width="{}" height="20" fill="{}"/>
     <rect width="{}" height="20" fill="url(#smooth)"/>
   </g>
@@ -107,35 +369,222 @@ impl Badge {
             left_width + right_width,
             left_width + right_width,
             left_width,
+            escape_xml(&self.options.subject_color),
+            left_width,
+            right_width,
+            escape_xml(&self.options.color),
+            left_width + right_width,
+            (left_width) / 2,
+            escape_xml(&self.options.subject),
+            (left_width) / 2,
+            escape_xml(&self.options.subject),
+            left_width + (right_width / 2),
+            escape_xml(&self.options.status),
+            left_width + (right_width / 2),
+            escape_xml(&self.options.status),
+            accessibility_attributes = self.accessibility_attributes(),
+            accessibility_elements = self.accessibility_elements());
+
+        svg
+    }
+
+
+    fn to_flat_square_svg(&self) -> String {
+        let Layout { left_width, right_width, .. } = self.layout();
+
+        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="20" {accessibility_attributes}>
+  {accessibility_elements}
+
+  <g shape-rendering="crispEdges">
+    <rect width="{}" height="20" fill="{}"/>
+    <rect x="{}" width="{}" height="20" fill="{}"/>
+  </g>
+
+  <g fill="#fff" text-anchor="middle" font-family="DejaVu Sans,Verdana,Geneva,sans-serif" font-size="11">
+    <text x="{}" y="14">{}</text>
+    <text x="{}" y="14">{}</text>
+  </g>
+</svg>"###,
+            left_width + right_width,
+            left_width,
+            escape_xml(&self.options.subject_color),
+            left_width,
+            right_width,
+            escape_xml(&self.options.color),
+            (left_width) / 2,
+            escape_xml(&self.options.subject),
+            left_width + (right_width / 2),
+            escape_xml(&self.options.status),
+            accessibility_attributes = self.accessibility_attributes(),
+            accessibility_elements = self.accessibility_elements());
+
+        svg
+    }
+
+
+    fn to_plastic_svg(&self) -> String {
+        let Layout { left_width, right_width, .. } = self.layout();
+
+        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="18" {accessibility_attributes}>
+  {accessibility_elements}
+
+  <linearGradient id="smooth" x2="0" y2="100%">
+    <stop offset="0" stop-color="#fff" stop-opacity=".7"/>
+    <stop offset=".1" stop-color="#aaa" stop-opacity=".1"/>
+    <stop offset=".9" stop-opacity=".3"/>
+    <stop offset="1" stop-opacity=".5"/>
+  </linearGradient>
+
+  <mask id="round">
+    <rect width="{}" height="18" rx="4" fill="#fff"/>
+  </mask>
+
+  <g mask="url(#round)">
+    <rect width="{}" height="18" fill="{}"/>
+    <rect x="{}" width="{}" height="18" fill="{}"/>
+    <rect width="{}" height="18" fill="url(#smooth)"/>
+  </g>
+
+  <g fill="#fff" text-anchor="middle" font-family="DejaVu Sans,Verdana,Geneva,sans-serif" font-size="11">
+    <text x="{}" y="14" fill="#010101" fill-opacity=".3">{}</text>
+    <text x="{}" y="13">{}</text>
+    <text x="{}" y="14" fill="#010101" fill-opacity=".3">{}</text>
+    <text x="{}" y="13">{}</text>
+  </g>
+</svg>"###,
+            left_width + right_width,
+            left_width + right_width,
+            left_width,
+            escape_xml(&self.options.subject_color),
             left_width,
             right_width,
-            self.options.color,
+            escape_xml(&self.options.color),
             left_width + right_width,
             (left_width) / 2,
-            self.options.subject,
+            escape_xml(&self.options.subject),
             (left_width) / 2,
-            self.options.subject,
+            escape_xml(&self.options.subject),
             left_width + (right_width / 2),
-            self.options.status,
+            escape_xml(&self.options.status),
             left_width + (right_width / 2),
-            self.options.status);
+            escape_xml(&self.options.status),
+            accessibility_attributes = self.accessibility_attributes(),
+            accessibility_elements = self.accessibility_elements());
+
+        svg
+    }
+
+
+    fn to_for_the_badge_svg(&self) -> String {
+        let Layout { subject, status, left_width, right_width, .. } = self.layout();
+
+        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="28" {accessibility_attributes}>
+  {accessibility_elements}
+
+  <g shape-rendering="crispEdges">
+    <rect width="{}" height="28" fill="{}"/>
+    <rect x="{}" width="{}" height="28" fill="{}"/>
+  </g>
+
+  <g fill="#fff" text-anchor="middle" font-family="DejaVu Sans,Verdana,Geneva,sans-serif" font-size="10" letter-spacing="1">
+    <text x="{}" y="18">{}</text>
+    <text x="{}" y="18" font-weight="bold">{}</text>
+  </g>
+</svg>"###,
+            left_width + right_width,
+            left_width,
+            escape_xml(&self.options.subject_color),
+            left_width,
+            right_width,
+            escape_xml(&self.options.color),
+            (left_width) / 2,
+            escape_xml(&subject),
+            left_width + (right_width / 2),
+            escape_xml(&status),
+            accessibility_attributes = self.accessibility_attributes(),
+            accessibility_elements = self.accessibility_elements());
+
+        svg
+    }
+
+
+    fn to_social_svg(&self) -> String {
+        let Layout { left_width, right_width, status_x: bubble_x, width, .. } = self.layout();
+
+        let svg = format!(r###"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="20" {accessibility_attributes}>
+  {accessibility_elements}
+
+  <linearGradient id="smooth" x2="0" y2="100%">
+    <stop offset="0" stop-color="#fcfcfc" stop-opacity="0"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+
+  <g stroke="#d5d5d5">
+    <rect stroke="none" fill="#fcfcfc" x="0.5" y="0.5" width="{}" height="19" rx="2"/>
+    <rect x="{}.5" y="0.5" width="{}" height="19" rx="2" fill="#fafafa"/>
+    <path d="M{}.5 6.5l-3 3v1l3 3" stroke="#d5d5d5" fill="#fafafa"/>
+  </g>
+
+  <rect x="0.5" y="0.5" width="{}" height="19" rx="2" fill="url(#smooth)"/>
+
+  <g fill="#333" text-anchor="middle" font-family="DejaVu Sans,Verdana,Geneva,sans-serif" font-size="11">
+    <text x="{}" y="15" fill="#fff">{}</text>
+    <text x="{}" y="14" font-weight="bold">{}</text>
+    <text x="{}" y="15" fill="#fff">{}</text>
+    <text x="{}" y="14">{}</text>
+  </g>
+</svg>"###,
+            width,
+            left_width - 1,
+            bubble_x,
+            right_width - 1,
+            bubble_x,
+            left_width - 1,
+            (left_width) / 2,
+            escape_xml(&self.options.subject),
+            (left_width) / 2,
+            escape_xml(&self.options.subject),
+            bubble_x + (right_width / 2),
+            escape_xml(&self.options.status),
+            bubble_x + (right_width / 2),
+            escape_xml(&self.options.status),
+            accessibility_attributes = self.accessibility_attributes(),
+            accessibility_elements = self.accessibility_elements());
 
         svg
     }
 
 
+    /// Text announced by screen readers, e.g. "build: passing"
+    fn accessible_title(&self) -> String {
+        format!("{}: {}", self.options.subject, self.options.status)
+    }
+
+
+    fn accessibility_attributes(&self) -> String {
+        format!(r#"role="img" aria-label="{}""#, escape_xml(&self.accessible_title()))
+    }
+
+
+    fn accessibility_elements(&self) -> String {
+        let title = format!("<title>{}</title>", escape_xml(&self.accessible_title()));
+        match self.options.description {
+            Some(ref description) => format!("{}\n  <desc>{}</desc>", title, escape_xml(description)),
+            None => title,
+        }
+    }
+
+
     fn calculate_width(&self, text: &str) -> u32 {
-        let glyphs: Vec<PositionedGlyph> =
-            DATA.font.layout(text, DATA.scale, DATA.offset).collect();
-        let width = glyphs.iter()
-            .rev()
-            .filter_map(|g| {
-                g.pixel_bounding_box()
-                    .map(|b| b.min.x as f32 + g.unpositioned().h_metrics().advance_width)
-            })
-            .next()
-            .unwrap_or(0.0);
-        (width + ((text.len() as f32 - 1f32) * 1.3)).ceil() as u32
+        let width = text.chars().map(char_width).sum::<f32>();
+        width.ceil() as u32
+    }
+
+
+    /// Width of text set at font size 10 with a letter spacing of 1
+    fn calculate_spaced_width(&self, text: &str) -> u32 {
+        let width = self.calculate_width(text) as f32 * 10. / FONT_SIZE;
+        (width + text.chars().count() as f32).ceil() as u32
     }
 }
 
@@ -152,8 +601,116 @@ mod tests {
     #[test]
     fn test_calculate_width() {
         let badge = Badge::new(options());
-        assert_eq!(badge.calculate_width("build"), 29);
-        assert_eq!(badge.calculate_width("passing"), 44);
+        assert_eq!(badge.calculate_width(""), 0);
+        assert!(badge.calculate_width("iiii") < badge.calculate_width("mmmm"));
+        assert!(badge.calculate_width("12 of 140 outdated") > badge.calculate_width("1 of 14 outdated"));
+    }
+
+    #[test]
+    fn test_calculate_width_matches_dejavu_metrics() {
+        // advance widths of each glyph as listed in the hmtx table of DejaVuSans.ttf,
+        // in units of 1/2048 em, so 11px wide text spans ceil(sum * 11 / 2048) pixels
+        let cases: [(&str, &[u32], u32); 4] = [
+            ("build", &[1300, 1298, 569, 569, 1300], 28),
+            ("passing", &[1300, 1255, 1067, 1067, 569, 1298, 1300], 43),
+            ("mmmm", &[1995, 1995, 1995, 1995], 43),
+            ("up to date", &[1298, 1300, 651, 803, 1253, 651, 1300, 1255, 803, 1260], 57),
+        ];
+        let badge = Badge::new(options());
+        for &(text, advances, width) in cases.iter() {
+            let units = advances.iter().sum::<u32>() as f32;
+            assert_eq!((units * FONT_SIZE / 2048.).ceil() as u32, width);
+            assert_eq!(badge.calculate_width(text), width, "width of {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_padding_matches_shields() {
+        // shields.io pads each text by 5px on either side, e.g. its flat
+        // `build | passing` badge has boxes of 37 and 51px around texts
+        // that are 27 and 41px wide in Verdana
+        let badge = Badge::new(options());
+        let layout = badge.layout();
+        assert_eq!(layout.left_width, 28 + 10);
+        assert_eq!(layout.right_width, 43 + 10);
+        assert_eq!(layout.width, 38 + 53);
+    }
+
+    #[test]
+    fn test_calculate_width_non_ascii() {
+        let badge = Badge::new(options());
+        assert!(badge.calculate_width("ä") > 0);
+        assert_eq!(badge.calculate_width("ä"), badge.calculate_width("a"));
+    }
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!("flat".parse::<BadgeStyle>(), Ok(BadgeStyle::Flat));
+        assert_eq!("flat-square".parse::<BadgeStyle>(), Ok(BadgeStyle::FlatSquare));
+        assert_eq!("plastic".parse::<BadgeStyle>(), Ok(BadgeStyle::Plastic));
+        assert_eq!("for-the-badge".parse::<BadgeStyle>(), Ok(BadgeStyle::ForTheBadge));
+        assert_eq!("social".parse::<BadgeStyle>(), Ok(BadgeStyle::Social));
+        assert!("rounded".parse::<BadgeStyle>().is_err());
+    }
+
+    #[test]
+    fn test_for_the_badge_is_uppercase() {
+        let badge = Badge::new(BadgeOptions {
+            style: BadgeStyle::ForTheBadge,
+            ..options()
+        });
+        let svg = badge.to_svg();
+        assert!(svg.contains(">BUILD<"));
+        assert!(svg.contains(">PASSING<"));
+        assert!(svg.contains(r#"height="28""#));
+    }
+
+    #[test]
+    fn test_escapes_text() {
+        let badge = Badge::new(BadgeOptions {
+            subject: "<script>".to_owned(),
+            status: "\"a\" & 'b'".to_owned(),
+            ..options()
+        });
+        let svg = badge.to_svg();
+        assert!(!svg.contains("<script>"));
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(svg.contains("&quot;a&quot; &amp; &#39;b&#39;"));
+    }
+
+    #[test]
+    fn test_accessible_svg() {
+        let svg = Badge::new(options()).to_svg();
+        assert!(svg.contains(r#"role="img" aria-label="build: passing""#));
+        assert!(svg.contains("<title>build: passing</title>"));
+        assert!(!svg.contains("<desc>"));
+
+        let badge = Badge::new(BadgeOptions {
+            style: BadgeStyle::ForTheBadge,
+            description: Some("Outdated: <foo>".to_owned()),
+            ..options()
+        });
+        let svg = badge.to_svg();
+        assert!(svg.contains("<title>build: passing</title>"));
+        assert!(svg.contains("<desc>Outdated: &lt;foo&gt;</desc>"));
+    }
+
+    #[test]
+    fn test_to_png_matches_svg_size() {
+        for &style in [BadgeStyle::Flat, BadgeStyle::ForTheBadge, BadgeStyle::Social].iter() {
+            let badge = Badge::new(BadgeOptions { style, ..options() });
+            let layout = badge.layout();
+            assert!(badge.to_svg().contains(&format!(r#"width="{}" height="{}""#, layout.width, layout.height)));
+
+            let png = badge.to_png(2);
+            assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+            assert_eq!(&png[16..20], &raster_size(layout.width * 2));
+            assert_eq!(&png[20..24], &raster_size(layout.height * 2));
+        }
+    }
+
+    fn raster_size(value: u32) -> [u8; 4] {
+        [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
     }
 
     #[test]
@@ -0,0 +1,40 @@
+extern crate rusttype;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use rusttype::{FontCollection, Scale, point};
+
+
+const FONT_DATA: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"),
+                                                        "/DejaVuSans.ttf"));
+const FONT_SIZE: f32 = 11.;
+
+
+fn main() {
+    let collection = FontCollection::from_bytes(FONT_DATA)
+        .expect("failed to parse font collection");
+    let font = collection.into_font()
+        .expect("failed to load font data");
+    // rusttype scales by the height from descent to ascent, not by the em size
+    let v_metrics = font.v_metrics_unscaled();
+    let scale = Scale::uniform(FONT_SIZE * (v_metrics.ascent - v_metrics.descent) / font.units_per_em() as f32);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join("widths.rs");
+    let mut f = File::create(&dest_path).unwrap();
+
+    writeln!(f, "/// Advance widths of the printable ASCII characters at {}px,", FONT_SIZE).unwrap();
+    writeln!(f, "/// starting with the space character").unwrap();
+    writeln!(f, "const ASCII_ADVANCE_WIDTHS: [f32; 95] = [").unwrap();
+    for code in 32u8..127 {
+        let text = (code as char).to_string();
+        let width = font.layout(&text, scale, point(0.0, 0.0))
+            .map(|g| g.unpositioned().h_metrics().advance_width)
+            .sum::<f32>();
+        writeln!(f, "    {:.4}, // {:?}", width, code as char).unwrap();
+    }
+    writeln!(f, "];").unwrap();
+}
@@ -0,0 +1,234 @@
+//! Minimal rasterizer and PNG encoder for badges
+
+
+/// RGBA image with straight (non-premultiplied) alpha, initially transparent
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+
+    /// Composites a color with the given coverage on top of a single pixel
+    pub fn blend(&mut self, x: i32, y: i32, color: [u8; 3], alpha: f32) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height || alpha <= 0. {
+            return;
+        }
+
+        let alpha = alpha.min(1.);
+        let index = ((y as u32 * self.width + x as u32) * 4) as usize;
+        let dest_alpha = self.pixels[index + 3] as f32 / 255.;
+        let out_alpha = alpha + dest_alpha * (1. - alpha);
+
+        for (channel, &src) in color.iter().enumerate() {
+            let dest = self.pixels[index + channel] as f32;
+            let out = (src as f32 * alpha + dest * dest_alpha * (1. - alpha)) / out_alpha;
+            self.pixels[index + channel] = out.round() as u8;
+        }
+        self.pixels[index + 3] = (out_alpha * 255.).round() as u8;
+    }
+
+
+    /// Fills the part of a rounded rectangle `(x, y, width, height)` between the
+    /// horizontal clip bounds, anti-aliasing the edges by supersampling every pixel
+    pub fn fill_rounded_rect(&mut self, rect: (f32, f32, f32, f32), radius: f32, clip: (f32, f32),
+                             color: [u8; 3]) {
+        const SAMPLES: u32 = 4;
+
+        let (x, y, width, height) = rect;
+        let (clip_x0, clip_x1) = clip;
+        let x0 = x.max(clip_x0).floor() as i32;
+        let x1 = (x + width).min(clip_x1).ceil() as i32;
+        let y0 = y.floor() as i32;
+        let y1 = (y + height).ceil() as i32;
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let mut covered = 0;
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let sample_x = px as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                        let sample_y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                        if (clip_x0..clip_x1).contains(&sample_x) &&
+                            in_rounded_rect(sample_x, sample_y, rect, radius) {
+                            covered += 1;
+                        }
+                    }
+                }
+                self.blend(px, py, color, covered as f32 / (SAMPLES * SAMPLES) as f32);
+            }
+        }
+    }
+
+
+    pub fn to_png(&self) -> Vec<u8> {
+        encode_png(self.width, self.height, &self.pixels)
+    }
+}
+
+
+fn in_rounded_rect(px: f32, py: f32, rect: (f32, f32, f32, f32), radius: f32) -> bool {
+    let (x, y, width, height) = rect;
+    if px < x || py < y || px >= x + width || py >= y + height {
+        return false;
+    }
+
+    // distance to the closest corner center, only relevant within the corner squares
+    let cx = px.max(x + radius).min(x + width - radius);
+    let cy = py.max(y + radius).min(y + height - radius);
+    let (dx, dy) = (px - cx, py - cy);
+    dx * dx + dy * dy <= radius * radius
+}
+
+
+/// Parses colors of the form `#rgb` or `#rrggbb`
+pub fn parse_hex_color(input: &str) -> Option<[u8; 3]> {
+    let hex = input.trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let component = |digits: &str| u8::from_str_radix(digits, 16).ok();
+
+    match hex.len() {
+        3 => {
+            let mut color = [0; 3];
+            for (i, c) in hex.chars().enumerate() {
+                color[i] = component(&c.to_string())? * 0x11;
+            }
+            Some(color)
+        },
+        6 => Some([component(&hex[0..2])?, component(&hex[2..4])?, component(&hex[4..6])?]),
+        _ => None,
+    }
+}
+
+
+/// Encodes RGBA pixels as PNG.
+///
+/// Badges are tiny, so the image data is stored in uncompressed deflate
+/// blocks instead of pulling in a compression library.
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut header = vec![];
+    header.extend_from_slice(&be_u32(width));
+    header.extend_from_slice(&be_u32(height));
+    // 8 bits per channel, RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let mut scanlines = Vec::with_capacity(rgba.len() + height as usize);
+    for row in rgba.chunks((width * 4) as usize) {
+        // filter type "none"
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&be_u32(data.len() as u32));
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&be_u32(crc));
+}
+
+
+/// Wraps data in a zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xffff;
+
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(if is_final { 1 } else { 0 });
+        out.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&be_u32(adler32(data)));
+    out
+}
+
+
+fn be_u32(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#4c1"), Some([0x44, 0xcc, 0x11]));
+        assert_eq!(parse_hex_color("e05d44"), Some([0xe0, 0x5d, 0x44]));
+        assert_eq!(parse_hex_color("#e05d4"), None);
+        assert_eq!(parse_hex_color("red"), None);
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let canvas = Canvas::new(3, 2);
+        let png = canvas.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+}
@@ -0,0 +1,212 @@
+extern crate hyper;
+extern crate hyper_tls;
+extern crate indexmap;
+extern crate shiny_robots;
+#[macro_use] extern crate slog;
+extern crate tokio_core;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use hyper::Client;
+use hyper_tls::HttpsConnector;
+use indexmap::IndexMap;
+use tokio_core::reactor::Core;
+
+use shiny_robots::engine::{Engine, AnalyzeDependenciesOutcome};
+use shiny_robots::models::crates::{AdvisoryKind, CrateName, AnalyzedDependency, AnalyzedDependencies};
+
+const USAGE: &str = "usage: deps-check [--deny-outdated] [--deny-insecure] [PATH]";
+
+struct Options {
+    path: PathBuf,
+    deny_outdated: bool,
+    deny_insecure: bool
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut path = None;
+    let mut deny_outdated = false;
+    let mut deny_insecure = false;
+
+    for arg in env::args().skip(1) {
+        match arg.as_ref() {
+            "--deny-outdated" => deny_outdated = true,
+            "--deny-insecure" => deny_insecure = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ if path.is_some() => return Err(format!("unexpected argument {}\n{}", arg, USAGE)),
+            _ => path = Some(PathBuf::from(arg))
+        }
+    }
+
+    Ok(Options {
+        path: path.unwrap_or_else(|| PathBuf::from(".")),
+        deny_outdated, deny_insecure
+    })
+}
+
+fn dependency_status(dep: &AnalyzedDependency) -> &'static str {
+    if dep.ignored.is_some() {
+        "ignored"
+    } else if dep.is_insecure() {
+        "insecure"
+    } else if dep.advisory_kind() == Some(AdvisoryKind::Unsound) {
+        "unsound"
+    } else if dep.is_unsatisfiable() {
+        "unsatisfiable"
+    } else if dep.is_yanked_only() {
+        "only yanked releases match"
+    } else if dep.is_outdated() {
+        "out of date"
+    } else if dep.is_locked_outdated() {
+        "lock out of date"
+    } else {
+        "up to date"
+    }
+}
+
+fn print_dependency_table(title: &str, deps: &IndexMap<CrateName, AnalyzedDependency>) {
+    if deps.is_empty() {
+        return;
+    }
+
+    println!("  {}:", title);
+    for (name, dep) in deps.iter() {
+        let latest = dep.latest.as_ref()
+            .map(|latest| latest.to_string())
+            .unwrap_or_else(|| "N/A".to_string());
+        let locked = dep.locked.as_ref()
+            .map(|locked| format!(" (locked {})", locked))
+            .unwrap_or_default();
+        let lag = dep.lag.as_ref()
+            .map(|lag| format!(", {} behind by {} release(s)", lag.kind.as_str(), lag.releases))
+            .unwrap_or_default();
+        let libyears = match dep.libyears {
+            Some(libyears) if libyears > 0.0 => format!(", {:.1} libyears", libyears),
+            _ => String::new()
+        };
+        println!("    {:<30} {:<12} latest {:<12} {}{}{}{}",
+            name.as_ref(), dep.required.to_string(), latest, dependency_status(dep), lag, libyears, locked);
+        for advisory in dep.advisories.iter() {
+            let cvss = advisory.cvss.as_ref()
+                .map(|cvss| format!(", CVSS {:.1}", cvss.base_score))
+                .unwrap_or_default();
+            println!("      {} ({}{}) {}", advisory.id, advisory.kind.as_str(), cvss, advisory.title);
+            if let Some(ref reason) = advisory.ignored {
+                println!("        ignored: {}", reason);
+            }
+        }
+        if let Some(ref reason) = dep.ignored {
+            println!("      ignored: {}", reason);
+        }
+        if let Some(ref safe_version) = dep.safe_version {
+            println!("      upgrade to {} or later", safe_version);
+        }
+    }
+}
+
+fn print_dependencies(crate_name: &CrateName, deps: &AnalyzedDependencies) {
+    println!("Crate {}", crate_name.as_ref());
+
+    if deps.count_total() == 0 {
+        println!("  No external dependencies");
+    } else {
+        println!("  {:.1} libyears behind in total", deps.libyears());
+    }
+
+    print_dependency_table("Dependencies", &deps.main);
+    print_dependency_table("Dev dependencies", &deps.dev);
+    print_dependency_table("Build dependencies", &deps.build);
+
+    for (target, target_deps) in deps.targets.iter() {
+        print_dependency_table(&format!("Dependencies for {}", target), &target_deps.main);
+        print_dependency_table(&format!("Dev dependencies for {}", target), &target_deps.dev);
+        print_dependency_table(&format!("Build dependencies for {}", target), &target_deps.build);
+    }
+
+    if !deps.transitive.is_empty() {
+        println!("  Transitive dependencies:");
+        for dep in deps.transitive.iter() {
+            let path = dep.path.iter()
+                .map(|parent| parent.as_ref())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            let status = match (dep.advisory_kind, dep.yanked) {
+                (Some(AdvisoryKind::Vulnerability), true) => "insecure, yanked".to_string(),
+                (Some(AdvisoryKind::Vulnerability), false) => "insecure".to_string(),
+                (Some(kind), true) => format!("{}, yanked", kind.as_str()),
+                (Some(kind), false) => kind.as_str().to_string(),
+                (None, _) => "yanked".to_string()
+            };
+            println!("    {:<30} {:<12} {} (via {} -> {})",
+                dep.name.as_ref(), dep.version.to_string(), status, crate_name.as_ref(), path);
+        }
+    }
+
+    println!();
+}
+
+fn print_report(outcome: &AnalyzeDependenciesOutcome) {
+    for &(ref crate_name, ref deps) in outcome.crates.iter() {
+        print_dependencies(crate_name, deps);
+    }
+
+    let (outdated, total) = outcome.outdated_ratio();
+    let (major_outdated, _) = outcome.major_outdated_ratio();
+    if outcome.any_insecure() {
+        println!("Status: insecure ({} of {} outdated)", outdated, total);
+    } else if outcome.any_unsatisfiable() {
+        println!("Status: unsatisfiable requirements");
+    } else if outcome.any_yanked_only() {
+        println!("Status: requirements that only match yanked releases");
+    } else if outdated > 0 {
+        println!("Status: {} of {} outdated ({} by a major release)", outdated, total, major_outdated);
+    } else {
+        println!("Status: up to date");
+    }
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let logger = slog::Logger::root(slog::Discard, o!());
+
+    let mut core = Core::new()
+        .expect("failed to create event loop");
+
+    let connector = HttpsConnector::new(4, &core.handle())
+        .expect("failed to create https connector");
+
+    let client = Client::configure()
+        .connector(connector)
+        .build(&core.handle());
+
+    let engine = Engine::new(client, logger, None);
+
+    let outcome = match core.run(engine.analyze_local_dependencies(options.path)) {
+        Ok(outcome) => outcome,
+        Err(err) => {
+            eprintln!("error: failed to analyze dependencies: {}", err);
+            process::exit(2);
+        }
+    };
+
+    print_report(&outcome);
+
+    let insecure_failure = options.deny_insecure && outcome.any_insecure();
+    // requirements that only match yanked releases or none at all need updating just as well
+    let outdated_failure = options.deny_outdated &&
+        (outcome.any_outdated() || outcome.any_yanked_only() || outcome.any_unsatisfiable());
+
+    if insecure_failure || outdated_failure {
+        process::exit(1);
+    }
+}
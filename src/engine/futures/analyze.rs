@@ -1,36 +1,58 @@
+use std::sync::Arc;
+
 use failure::Error;
 use futures::{Future, Poll, Stream};
+use futures::future::join_all;
 use futures::stream::futures_unordered;
 
-use ::models::crates::{AnalyzedDependencies, CrateDeps};
+use ::models::crates::{AnalyzedDependencies, CrateDeps, CrateLockfile};
+use ::models::policy::Policy;
 
 use super::super::Engine;
 use super::super::machines::analyzer::DependencyAnalyzer;
+use super::resolve::ResolveTransitiveFuture;
 
 pub struct AnalyzeDependenciesFuture {
     inner: Box<Future<Item=AnalyzedDependencies, Error=Error>>
 }
 
 impl AnalyzeDependenciesFuture {
-    pub fn new(engine: Engine, deps: CrateDeps) -> Self {
+    pub fn new(engine: Engine, deps: CrateDeps, lockfile: Option<Arc<CrateLockfile>>, policy: Arc<Policy>) -> Self {
         let future = engine.fetch_advisory_db().and_then(move |advisory_db| {
-            let analyzer = DependencyAnalyzer::new(&deps, Some(advisory_db));
+            let transitive_future = ResolveTransitiveFuture::new(engine.clone(), &deps,
+                Some(advisory_db.db.clone()), advisory_db.metadata.clone(), policy.clone());
+
+            let mut analyzer = DependencyAnalyzer::new(&deps, Some(advisory_db.db.clone()));
+            analyzer.set_advisory_metadata(advisory_db.metadata.clone());
+            analyzer.set_policy(policy);
+            if let Some(ref lockfile) = lockfile {
+                analyzer.set_lockfile(lockfile);
+            }
 
-            let main_deps = deps.main.into_iter().filter_map(|(name, dep)| {
-                if dep.is_external() { Some(name) } else { None }
-            });
-            let dev_deps = deps.dev.into_iter().filter_map(|(name, dep)| {
-                if dep.is_external() { Some(name) } else { None }
-            });
-            let build_deps = deps.build.into_iter().filter_map(|(name, dep)| {
-                if dep.is_external() { Some(name) } else { None }
-            });
+            let packages = deps.iter().filter_map(|(name, dep)| {
+                dep.external_package(name).map(|(package, _)| package.clone())
+            }).collect::<Vec<_>>();
 
-            let release_futures = engine.fetch_releases(main_deps.chain(dev_deps).chain(build_deps));
+            let release_futures = engine.fetch_releases(packages.clone());
+            let release_dates_future = join_all(engine.fetch_release_dates(packages));
 
             futures_unordered(release_futures)
                 .fold(analyzer, |mut analyzer, releases| { analyzer.process(releases); Ok(analyzer) as Result<_, Error> })
-                .map(|analyzer| analyzer.finalize())
+                .join(release_dates_future)
+                .map(|(mut analyzer, release_dates)| {
+                    for (name, dates) in release_dates {
+                        if let Some(dates) = dates {
+                            analyzer.set_release_dates(name, dates);
+                        }
+                    }
+                    analyzer.finalize()
+                })
+                .join(transitive_future)
+                .map(|(mut analyzed_deps, (transitive, truncated))| {
+                    analyzed_deps.transitive = transitive;
+                    analyzed_deps.transitive_truncated = truncated;
+                    analyzed_deps
+                })
         });
 
         AnalyzeDependenciesFuture {
@@ -5,30 +5,54 @@ use futures::{Async, Future, Poll, Stream};
 use futures::stream::FuturesOrdered;
 use relative_path::RelativePathBuf;
 
-use ::models::repo::RepoPath;
-
-use super::super::Engine;
-use super::super::machines::crawler::ManifestCrawler;
+use super::super::{Engine, ManifestSource};
+use super::super::machines::crawler::{ManifestCrawler, ManifestCrawlerStepOutput};
 pub use super::super::machines::crawler::ManifestCrawlerOutput;
 
+enum CrawlItem {
+    Manifest(RelativePathBuf, String),
+    Directory(RelativePathBuf, Vec<String>)
+}
+
 pub struct CrawlManifestFuture {
-    repo_path: RepoPath,
+    source: ManifestSource,
     engine: Engine,
     crawler: ManifestCrawler,
-    futures: FuturesOrdered<Box<Future<Item=(RelativePathBuf, String), Error=Error>>>
+    futures: FuturesOrdered<Box<Future<Item=CrawlItem, Error=Error>>>,
+    lockfile_future: Option<Box<Future<Item=Option<String>, Error=Error>>>,
+    policy_future: Option<Box<Future<Item=Option<String>, Error=Error>>>
 }
 
 impl CrawlManifestFuture {
-    pub fn new(engine: &Engine, repo_path: RepoPath, entry_point: RelativePathBuf) -> Self {
-        let future: Box<Future<Item=_, Error=_>> = Box::new(engine.retrieve_manifest_at_path(&repo_path, &entry_point)
-            .map(move |contents| (entry_point, contents)));
+    pub fn new(engine: &Engine, source: ManifestSource, entry_point: RelativePathBuf) -> Self {
+        let lockfile_future: Box<Future<Item=_, Error=_>> = Box::new(engine.retrieve_lockfile_at_path(&source, &entry_point)
+            .then(|result| Ok(result.ok()) as Result<_, Error>));
+        let policy_future: Box<Future<Item=_, Error=_>> = Box::new(engine.retrieve_policy_at_path(&source, &entry_point)
+            .then(|result| Ok(result.ok()) as Result<_, Error>));
+        let future: Box<Future<Item=_, Error=_>> = Box::new(engine.retrieve_manifest_at_path(&source, &entry_point)
+            .map(move |contents| CrawlItem::Manifest(entry_point, contents)));
         let engine = engine.clone();
         let crawler = ManifestCrawler::new();
         let mut futures = FuturesOrdered::new();
         futures.push(future);
 
         CrawlManifestFuture {
-            repo_path, engine, crawler, futures
+            source, engine, crawler, futures,
+            lockfile_future: Some(lockfile_future),
+            policy_future: Some(policy_future)
+        }
+    }
+
+    fn push_step_output(&mut self, output: ManifestCrawlerStepOutput) {
+        for path in output.paths_of_interest.into_iter() {
+            let future: Box<Future<Item=_, Error=_>> = Box::new(self.engine.retrieve_manifest_at_path(&self.source, &path)
+                .map(move |contents| CrawlItem::Manifest(path, contents)));
+            self.futures.push(future);
+        }
+        for path in output.directories_of_interest.into_iter() {
+            let future: Box<Future<Item=_, Error=_>> = Box::new(self.engine.list_subdirectories_at_path(&self.source, &path)
+                .map(move |subdirectories| CrawlItem::Directory(path, subdirectories)));
+            self.futures.push(future);
         }
     }
 }
@@ -38,18 +62,43 @@ impl Future for CrawlManifestFuture {
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(mut lockfile_future) = self.lockfile_future.take() {
+            match lockfile_future.poll()? {
+                Async::Ready(Some(raw_lockfile)) => {
+                    // the manifests can still be analyzed without the lockfile
+                    if let Err(err) = self.crawler.step_lockfile(raw_lockfile) {
+                        warn!(self.engine.logger, "failed to parse lockfile: {}", err);
+                    }
+                },
+                Async::Ready(None) => {},
+                Async::NotReady => self.lockfile_future = Some(lockfile_future)
+            }
+        }
+
+        if let Some(mut policy_future) = self.policy_future.take() {
+            match policy_future.poll()? {
+                Async::Ready(Some(raw_policy)) => self.crawler.step_policy(raw_policy),
+                Async::Ready(None) => {},
+                Async::NotReady => self.policy_future = Some(policy_future)
+            }
+        }
+
         match try_ready!(self.futures.poll()) {
             None => {
+                if self.lockfile_future.is_some() || self.policy_future.is_some() {
+                    return Ok(Async::NotReady);
+                }
                 let crawler = mem::replace(&mut self.crawler, ManifestCrawler::new());
                 Ok(Async::Ready(crawler.finalize()))
             },
-            Some((path, raw_manifest)) => {
+            Some(CrawlItem::Manifest(path, raw_manifest)) => {
                 let output = self.crawler.step(path, raw_manifest)?;
-                for path in output.paths_of_interest.into_iter() {
-                    let future: Box<Future<Item=_, Error=_>> = Box::new(self.engine.retrieve_manifest_at_path(&self.repo_path, &path)
-                        .map(move |contents| (path, contents)));
-                    self.futures.push(future);
-                }
+                self.push_step_output(output);
+                self.poll()
+            },
+            Some(CrawlItem::Directory(path, subdirectories)) => {
+                let output = self.crawler.step_directory(path, subdirectories);
+                self.push_step_output(output);
                 self.poll()
             }
         }
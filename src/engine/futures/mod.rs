@@ -1,5 +1,6 @@
 mod crawl;
 mod analyze;
+mod resolve;
 
 pub use self::crawl::CrawlManifestFuture;
 pub use self::analyze::AnalyzeDependenciesFuture;
@@ -0,0 +1,96 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::sync::Arc;
+
+use failure::Error;
+use futures::{Async, Future, Poll, Stream};
+use futures::stream::FuturesUnordered;
+use rustsec::db::AdvisoryDatabase;
+use tokio_service::Service;
+
+use ::models::crates::{AdvisoryMetadata, CrateDeps, CrateName, CrateRelease, TransitiveDependency};
+use ::models::policy::Policy;
+
+use super::super::Engine;
+use super::super::machines::resolver::TransitiveResolver;
+
+/// Upper bound on the crates queried for a single dependency tree
+const MAX_QUERIED_CRATES: usize = 300;
+/// Upper bound on the index queries in flight for a single dependency tree
+const MAX_CONCURRENT_QUERIES: usize = 8;
+
+pub struct ResolveTransitiveFuture {
+    engine: Engine,
+    resolver: TransitiveResolver,
+    queue: VecDeque<CrateName>,
+    queried: usize,
+    truncated: bool,
+    futures: FuturesUnordered<Box<Future<Item=(CrateName, Vec<CrateRelease>), Error=Error>>>
+}
+
+impl ResolveTransitiveFuture {
+    pub fn new(engine: Engine, deps: &CrateDeps, advisory_db: Option<Arc<AdvisoryDatabase>>,
+               advisory_metadata: Arc<HashMap<String, AdvisoryMetadata>>, policy: Arc<Policy>) -> Self {
+        let mut resolver = TransitiveResolver::new(advisory_db);
+        resolver.set_advisory_metadata(advisory_metadata);
+        resolver.set_policy(policy);
+        let output = resolver.start(deps);
+
+        let mut future = ResolveTransitiveFuture {
+            engine, resolver,
+            queue: output.crates_of_interest.into_iter().collect(),
+            queried: 0,
+            truncated: false,
+            futures: FuturesUnordered::new()
+        };
+        future.push_queries();
+        future
+    }
+
+    /// Starts queued queries, up to the concurrency limit.
+    /// Once the crate limit is hit, the rest of the tree is left unresolved.
+    fn push_queries(&mut self) {
+        while self.futures.len() < MAX_CONCURRENT_QUERIES {
+            let name = match self.queue.pop_front() {
+                Some(name) => name,
+                None => return
+            };
+            if self.queried >= MAX_QUERIED_CRATES {
+                self.truncated = true;
+                self.queue.clear();
+                return;
+            }
+            self.queried += 1;
+            self.push_query(name);
+        }
+    }
+
+    fn push_query(&mut self, name: CrateName) {
+        // a crate that can't be queried shouldn't fail the whole analysis,
+        // so it is treated as if it had no releases at all
+        let future: Box<Future<Item=_, Error=_>> = Box::new(self.engine.query_crate.call(name.clone())
+            .then(move |result| Ok((name, result.map(|resp| resp.releases.clone()).unwrap_or_default())) as Result<_, Error>));
+        self.futures.push(future);
+    }
+}
+
+impl Future for ResolveTransitiveFuture {
+    /// Flagged dependencies, and whether the tree was only resolved partially
+    type Item = (Vec<TransitiveDependency>, bool);
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match try_ready!(self.futures.poll()) {
+            None => {
+                let resolver = mem::replace(&mut self.resolver, TransitiveResolver::new(None));
+                Ok(Async::Ready((resolver.finalize(), self.truncated)))
+            },
+            Some((name, releases)) => {
+                let output = self.resolver.step(name, releases);
+                self.queue.extend(output.crates_of_interest);
+                self.push_queries();
+                self.poll()
+            }
+        }
+    }
+}
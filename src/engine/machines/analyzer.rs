@@ -1,24 +1,83 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use rustsec::db::AdvisoryDatabase;
 use semver::Version;
 
-use ::models::crates::{CrateDeps, CrateRelease, CrateName, AnalyzedDependency, AnalyzedDependencies};
+use ::models::crates::{Advisory, AdvisoryMetadata, CrateDeps, CrateRelease, CrateName, CrateLockfile, AnalyzedDependency, AnalyzedDependencies, Lag, LagKind};
+use ::models::policy::Policy;
 
 pub struct DependencyAnalyzer {
     deps: AnalyzedDependencies,
-    advisory_db: Option<Arc<AdvisoryDatabase>>
+    advisory_db: Option<Arc<AdvisoryDatabase>>,
+    advisory_metadata: Arc<HashMap<String, AdvisoryMetadata>>,
+    policy: Arc<Policy>,
+    /// Stable releases seen per package, to find safe versions to upgrade to
+    /// and count the releases a dependency trails by
+    releases: HashMap<CrateName, Vec<Version>>,
+    release_dates: HashMap<CrateName, HashMap<Version, DateTime<Utc>>>
 }
 
 impl DependencyAnalyzer {
     pub fn new(deps: &CrateDeps, advisory_db: Option<Arc<AdvisoryDatabase>>) -> DependencyAnalyzer {
         DependencyAnalyzer {
             deps: AnalyzedDependencies::new(deps),
-            advisory_db
+            advisory_db,
+            advisory_metadata: Arc::new(HashMap::new()),
+            policy: Arc::new(Policy::default()),
+            releases: HashMap::new(),
+            release_dates: HashMap::new()
         }
     }
 
-    fn process_single(name: &CrateName, dep: &mut AnalyzedDependency, ver: &Version, advisory_db: Option<&AdvisoryDatabase>) {
+    pub fn set_policy(&mut self, policy: Arc<Policy>) {
+        self.policy = policy;
+    }
+
+    pub fn set_advisory_metadata(&mut self, advisory_metadata: Arc<HashMap<String, AdvisoryMetadata>>) {
+        self.advisory_metadata = advisory_metadata;
+    }
+
+    /// Publication dates of a package's versions, to compute libyears
+    pub fn set_release_dates(&mut self, name: CrateName, dates: HashMap<Version, DateTime<Utc>>) {
+        self.release_dates.insert(name, dates);
+    }
+
+    fn find_advisories(name: &CrateName, ver: &Version, advisory_db: Option<&AdvisoryDatabase>, advisory_metadata: &HashMap<String, AdvisoryMetadata>) -> Vec<Advisory> {
+        advisory_db.map(|db| {
+            db.find_vulns_for_crate(name.as_ref(), ver).into_iter().map(|advisory| {
+                let metadata = advisory_metadata.get(&advisory.id).cloned().unwrap_or_default();
+                Advisory {
+                    id: advisory.id.clone(),
+                    kind: metadata.kind,
+                    cvss: metadata.cvss,
+                    title: advisory.title.clone(),
+                    url: advisory.url.clone(),
+                    patched_versions: advisory.patched_versions.clone(),
+                    ignored: None
+                }
+            }).collect()
+        }).unwrap_or_default()
+    }
+
+    fn lock_single(name: &CrateName, dep: &mut AnalyzedDependency, lockfile: &CrateLockfile, advisory_db: Option<&AdvisoryDatabase>, advisory_metadata: &HashMap<String, AdvisoryMetadata>) {
+        if let Some(locked) = lockfile.find_locked(name, &dep.required) {
+            dep.advisories = DependencyAnalyzer::find_advisories(name, locked, advisory_db, advisory_metadata);
+            dep.locked = Some(locked.clone());
+        }
+    }
+
+    pub fn set_lockfile(&mut self, lockfile: &CrateLockfile) {
+        let advisory_db = self.advisory_db.as_ref().map(|r| r.as_ref());
+        let advisory_metadata = &*self.advisory_metadata;
+        for (name, dep) in self.deps.iter_mut() {
+            let package = dep.package_name(name).clone();
+            DependencyAnalyzer::lock_single(&package, dep, lockfile, advisory_db, advisory_metadata)
+        }
+    }
+
+    fn process_single(name: &CrateName, dep: &mut AnalyzedDependency, ver: &Version, advisory_db: Option<&AdvisoryDatabase>, advisory_metadata: &HashMap<String, AdvisoryMetadata>) {
         if dep.required.matches(&ver) {
             if let Some(ref mut current_latest_that_matches) = dep.latest_that_matches {
                 if *current_latest_that_matches < *ver {
@@ -28,8 +87,13 @@ impl DependencyAnalyzer {
                 dep.latest_that_matches = Some(ver.clone());
             }
 
-            if !advisory_db.map(|db| db.find_vulns_for_crate(name.as_ref(), ver).is_empty()).unwrap_or(true) {
-                dep.insecure = true;
+            // with a lockfile, only the exact locked version is checked for advisories
+            if dep.locked.is_none() {
+                for advisory in DependencyAnalyzer::find_advisories(name, ver, advisory_db, advisory_metadata) {
+                    if !dep.advisories.iter().any(|known| known.id == advisory.id) {
+                        dep.advisories.push(advisory);
+                    }
+                }
             }
         }
         if !ver.is_prerelease() {
@@ -45,29 +109,119 @@ impl DependencyAnalyzer {
 
     pub fn process<I: IntoIterator<Item=CrateRelease>>(&mut self, releases: I) {
         let advisory_db = self.advisory_db.as_ref().map(|r| r.as_ref());
-        for release in releases.into_iter().filter(|r| !r.yanked) {
-            if let Some(main_dep) = self.deps.main.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(&release.name, main_dep, &release.version, advisory_db)
+        let advisory_metadata = &*self.advisory_metadata;
+        for release in releases.into_iter() {
+            // yanked releases only tell apart requirements that can't be satisfied anymore
+            if release.yanked {
+                for (name, dep) in self.deps.iter_mut() {
+                    if *dep.package_name(name) == release.name && dep.required.matches(&release.version) {
+                        dep.matches_yanked = true;
+                    }
+                }
+                continue;
             }
-            if let Some(dev_dep) = self.deps.dev.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(&release.name, dev_dep, &release.version, advisory_db)
+
+            if !release.version.is_prerelease() {
+                self.releases.entry(release.name.clone()).or_insert_with(Vec::new).push(release.version.clone());
             }
-            if let Some(build_dep) = self.deps.build.get_mut(&release.name) {
-                DependencyAnalyzer::process_single(&release.name, build_dep, &release.version, advisory_db)
+
+            // dependencies are keyed by their declared name, which may be an alias
+            for (name, dep) in self.deps.iter_mut() {
+                if *dep.package_name(name) == release.name {
+                    DependencyAnalyzer::process_single(&release.name, dep, &release.version, advisory_db, advisory_metadata)
+                }
             }
         }
     }
 
-    pub fn finalize(self) -> AnalyzedDependencies {
+    fn apply_policy(name: &CrateName, dep: &mut AnalyzedDependency, policy: &Policy) {
+        dep.ignored = policy.crate_ignore_reason(name).map(|reason| reason.to_string());
+
+        for advisory in dep.advisories.iter_mut() {
+            advisory.ignored = policy.advisory_ignore_reason(&advisory.id).map(|reason| reason.to_string());
+        }
+
+        if policy.allow_minor_outdated {
+            dep.outdated_allowed = match dep.lag {
+                Some(ref lag) => lag.kind != LagKind::Major,
+                None => false
+            };
+        }
+    }
+
+    fn compute_lag(dep: &AnalyzedDependency, versions: &[Version]) -> Option<Lag> {
+        match (&dep.latest_that_matches, &dep.latest) {
+            (&Some(ref latest_that_matches), &Some(ref latest)) => {
+                LagKind::between(latest_that_matches, latest).map(|kind| {
+                    let releases = versions.iter()
+                        .filter(|version| *version > latest_that_matches && *version <= latest)
+                        .count();
+                    Lag { kind, releases }
+                })
+            },
+            _ => None
+        }
+    }
+
+    fn compute_libyears(dep: &AnalyzedDependency, dates: &HashMap<Version, DateTime<Utc>>) -> Option<f64> {
+        let current = dep.locked.as_ref().or_else(|| dep.latest_that_matches.as_ref())?;
+        let latest = dep.latest.as_ref()?;
+        let (current_date, latest_date) = (dates.get(current)?, dates.get(latest)?);
+
+        let days = latest_date.signed_duration_since(*current_date).num_days().max(0);
+        Some(days as f64 / 365.25)
+    }
+
+    pub fn finalize(mut self) -> AnalyzedDependencies {
+        let releases = &self.releases;
+        let release_dates = &self.release_dates;
+        let policy = &*self.policy;
+        for (name, dep) in self.deps.iter_mut() {
+            let package = dep.package_name(name).clone();
+            let lag = DependencyAnalyzer::compute_lag(dep, releases.get(&package).map(|versions| versions.as_slice()).unwrap_or(&[]));
+            dep.lag = lag;
+            let libyears = release_dates.get(&package).and_then(|dates| DependencyAnalyzer::compute_libyears(dep, dates));
+            dep.libyears = libyears;
+            DependencyAnalyzer::apply_policy(&package, dep, policy);
+
+            if !dep.advisories.is_empty() {
+                let safe_version = releases.get(&package).and_then(|versions| {
+                    versions.iter()
+                        .filter(|version| dep.advisories.iter().all(|advisory| advisory.is_patched(version)))
+                        .min()
+                        .cloned()
+                });
+                dep.safe_version = safe_version;
+            }
+        }
         self.deps
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use models::crates::{CrateDep, CrateDeps, CrateRelease};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+    use rustsec::db::AdvisoryDatabase;
+
+    use models::crates::{AdvisoryKind, AdvisoryMetadata, CrateDep, CrateDeps, CrateLockfile, CrateRelease, Lag, LagKind};
+    use models::policy::Policy;
+    use engine::machines::testing::release;
     use super::DependencyAnalyzer;
 
+    const ADVISORIES: &str = r#"
+[[advisory]]
+id = "RUSTSEC-2018-0001"
+package = "untrusted"
+patched_versions = [">= 0.6.2"]
+date = "2018-06-21"
+url = "https://github.com/briansmith/untrusted/pull/20"
+title = "An integer underflow could lead to panic"
+description = "An integer underflow could lead to panic."
+"#;
+
     #[test]
     fn tracks_latest_without_matching() {
         let mut deps = CrateDeps::default();
@@ -75,8 +229,8 @@ mod tests {
 
         let mut analyzer = DependencyAnalyzer::new(&deps, None);
         analyzer.process(vec![
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.0".parse().unwrap(), deps: Default::default(), yanked: false },
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.1".parse().unwrap(), deps: Default::default(), yanked: false }
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.10.1", CrateDeps::default())
         ]);
 
         let analyzed = analyzer.finalize();
@@ -92,9 +246,9 @@ mod tests {
 
         let mut analyzer = DependencyAnalyzer::new(&deps, None);
         analyzer.process(vec![
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.0".parse().unwrap(), deps: Default::default(), yanked: false },
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.1".parse().unwrap(), deps: Default::default(), yanked: false },
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.11.0".parse().unwrap(), deps: Default::default(), yanked: false }
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.10.1", CrateDeps::default()),
+            release("hyper", "0.11.0", CrateDeps::default())
         ]);
 
         let analyzed = analyzer.finalize();
@@ -103,6 +257,56 @@ mod tests {
         assert_eq!(analyzed.main.get("hyper").unwrap().latest, Some("0.11.0".parse().unwrap()));
     }
 
+    #[test]
+    fn tracks_lag() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("log".parse().unwrap(), CrateDep::External("~1.1.0".parse().unwrap()));
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.10.0".parse().unwrap()));
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process(vec![
+            release("log", "1.1.0", CrateDeps::default()),
+            release("log", "1.2.0", CrateDeps::default()),
+            release("log", "1.3.0-beta", CrateDeps::default()),
+            release("log", "1.3.0", CrateDeps::default()),
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.11.0", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.main.get("log").unwrap().lag, Some(Lag { kind: LagKind::Minor, releases: 2 }));
+        assert_eq!(analyzed.main.get("hyper").unwrap().lag, Some(Lag { kind: LagKind::Major, releases: 1 }));
+        assert_eq!(analyzed.count_outdated(), 2);
+        assert_eq!(analyzed.count_major_outdated(), 1);
+    }
+
+    #[test]
+    fn tracks_libyears() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.10.0".parse().unwrap()));
+
+        let mut lockfile = CrateLockfile::default();
+        lockfile.packages.insert("hyper".parse().unwrap(), vec!["0.10.0".parse().unwrap()]);
+
+        let mut dates = HashMap::new();
+        dates.insert("0.10.0".parse().unwrap(), Utc.ymd(2016, 1, 1).and_hms(0, 0, 0));
+        dates.insert("0.11.0".parse().unwrap(), Utc.ymd(2017, 7, 2).and_hms(12, 0, 0));
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.set_lockfile(&lockfile);
+        analyzer.set_release_dates("hyper".parse().unwrap(), dates);
+        analyzer.process(vec![
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.11.0", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.main.get("hyper").unwrap().libyears, Some(548.0 / 365.25));
+        assert_eq!(analyzed.libyears(), 548.0 / 365.25);
+    }
+
     #[test]
     fn skips_yanked_releases() {
         let mut deps = CrateDeps::default();
@@ -110,8 +314,8 @@ mod tests {
 
         let mut analyzer = DependencyAnalyzer::new(&deps, None);
         analyzer.process(vec![
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.0".parse().unwrap(), deps: Default::default(), yanked: false },
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.1".parse().unwrap(), deps: Default::default(), yanked: true },
+            release("hyper", "0.10.0", CrateDeps::default()),
+            CrateRelease { yanked: true, ..release("hyper", "0.10.1", CrateDeps::default()) },
         ]);
 
         let analyzed = analyzer.finalize();
@@ -120,6 +324,30 @@ mod tests {
         assert_eq!(analyzed.main.get("hyper").unwrap().latest, Some("0.10.0".parse().unwrap()));
     }
 
+    #[test]
+    fn tells_apart_yanked_only_and_unsatisfiable_requirements() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("=0.10.1".parse().unwrap()));
+        deps.main.insert("log".parse().unwrap(), CrateDep::External("^2.0.0".parse().unwrap()));
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process(vec![
+            release("hyper", "0.10.0", CrateDeps::default()),
+            CrateRelease { yanked: true, ..release("hyper", "0.10.1", CrateDeps::default()) },
+            release("log", "1.0.0", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+        let hyper = analyzed.main.get("hyper").unwrap();
+        let log = analyzed.main.get("log").unwrap();
+
+        assert!(hyper.is_yanked_only());
+        assert!(!hyper.is_unsatisfiable());
+        assert!(log.is_unsatisfiable());
+        assert!(!log.is_yanked_only());
+        assert_eq!(analyzed.count_outdated(), 0);
+    }
+
     #[test]
     fn skips_prereleases() {
         let mut deps = CrateDeps::default();
@@ -127,8 +355,8 @@ mod tests {
 
         let mut analyzer = DependencyAnalyzer::new(&deps, None);
         analyzer.process(vec![
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.0".parse().unwrap(), deps: Default::default(), yanked: false },
-            CrateRelease { name: "hyper".parse().unwrap(), version: "0.10.1-alpha".parse().unwrap(), deps: Default::default(), yanked: false },
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.10.1-alpha", CrateDeps::default()),
         ]);
 
         let analyzed = analyzer.finalize();
@@ -136,4 +364,153 @@ mod tests {
         assert_eq!(analyzed.main.get("hyper").unwrap().latest_that_matches, Some("0.10.0".parse().unwrap()));
         assert_eq!(analyzed.main.get("hyper").unwrap().latest, Some("0.10.0".parse().unwrap()));
     }
+
+    #[test]
+    fn tracks_locked_versions() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.10.0".parse().unwrap()));
+
+        let mut lockfile = CrateLockfile::default();
+        lockfile.packages.insert("hyper".parse().unwrap(), vec!["0.10.0".parse().unwrap()]);
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.set_lockfile(&lockfile);
+        analyzer.process(vec![
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.10.1", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.main.get("hyper").unwrap().locked, Some("0.10.0".parse().unwrap()));
+        assert!(analyzed.main.get("hyper").unwrap().is_locked_outdated());
+        assert!(!analyzed.main.get("hyper").unwrap().is_outdated());
+    }
+
+    #[test]
+    fn tracks_renamed_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("http02".parse().unwrap(), CrateDep::Renamed("http".parse().unwrap(), "^0.2.0".parse().unwrap()));
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process(vec![
+            release("http", "0.2.1", CrateDeps::default()),
+            release("http", "1.0.0", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.main.get("http02").unwrap().package, Some("http".parse().unwrap()));
+        assert_eq!(analyzed.main.get("http02").unwrap().latest_that_matches, Some("0.2.1".parse().unwrap()));
+        assert_eq!(analyzed.main.get("http02").unwrap().latest, Some("1.0.0".parse().unwrap()));
+    }
+
+    #[test]
+    fn tracks_target_specific_dependencies() {
+        let mut target_deps = CrateDeps::default();
+        target_deps.main.insert("winapi".parse().unwrap(), CrateDep::External("^0.2.0".parse().unwrap()));
+
+        let mut deps = CrateDeps::default();
+        deps.targets.insert("cfg(windows)".to_string(), target_deps);
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, None);
+        analyzer.process(vec![
+            release("winapi", "0.2.8", CrateDeps::default()),
+            release("winapi", "0.3.4", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+
+        assert_eq!(analyzed.targets["cfg(windows)"].main.get("winapi").unwrap().latest_that_matches, Some("0.2.8".parse().unwrap()));
+        assert_eq!(analyzed.count_total(), 1);
+        assert_eq!(analyzed.count_outdated(), 1);
+    }
+
+    #[test]
+    fn tracks_advisories_and_safe_version() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("untrusted".parse().unwrap(), CrateDep::External("^0.6.0".parse().unwrap()));
+
+        let mut lockfile = CrateLockfile::default();
+        lockfile.packages.insert("untrusted".parse().unwrap(), vec!["0.6.1".parse().unwrap()]);
+
+        let advisory_db = AdvisoryDatabase::from_toml(ADVISORIES).unwrap();
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, Some(Arc::new(advisory_db)));
+        analyzer.set_lockfile(&lockfile);
+        analyzer.process(vec![
+            release("untrusted", "0.6.1", CrateDeps::default()),
+            release("untrusted", "0.6.3", CrateDeps::default()),
+            release("untrusted", "0.6.2", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+        let dep = analyzed.main.get("untrusted").unwrap();
+
+        assert!(dep.is_insecure());
+        assert_eq!(dep.advisories.len(), 1);
+        assert_eq!(dep.advisories[0].id, "RUSTSEC-2018-0001");
+        assert_eq!(dep.advisories[0].kind, AdvisoryKind::Vulnerability);
+        assert_eq!(dep.safe_version, Some("0.6.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn tracks_advisory_kinds() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("untrusted".parse().unwrap(), CrateDep::External("=0.6.1".parse().unwrap()));
+
+        let advisory_db = AdvisoryDatabase::from_toml(ADVISORIES).unwrap();
+        let mut advisory_metadata = HashMap::new();
+        advisory_metadata.insert("RUSTSEC-2018-0001".to_string(), AdvisoryMetadata { kind: AdvisoryKind::Unmaintained, cvss: None });
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, Some(Arc::new(advisory_db)));
+        analyzer.set_advisory_metadata(Arc::new(advisory_metadata));
+        analyzer.process(vec![
+            release("untrusted", "0.6.1", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+        let dep = analyzed.main.get("untrusted").unwrap();
+
+        assert_eq!(dep.advisory_kind(), Some(AdvisoryKind::Unmaintained));
+        assert!(!dep.is_insecure());
+        assert_eq!(analyzed.count_insecure(), 0);
+    }
+
+    #[test]
+    fn applies_policy() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("untrusted".parse().unwrap(), CrateDep::External("=0.6.1".parse().unwrap()));
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.10.0".parse().unwrap()));
+        deps.main.insert("log".parse().unwrap(), CrateDep::External("=1.0.0".parse().unwrap()));
+
+        let mut policy = Policy::default();
+        policy.allow_minor_outdated = true;
+        policy.ignored_advisories.insert("RUSTSEC-2018-0001".to_string(), "not reachable".to_string());
+
+        let advisory_db = AdvisoryDatabase::from_toml(ADVISORIES).unwrap();
+
+        let mut analyzer = DependencyAnalyzer::new(&deps, Some(Arc::new(advisory_db)));
+        analyzer.set_policy(Arc::new(policy));
+        analyzer.process(vec![
+            release("untrusted", "0.6.1", CrateDeps::default()),
+            release("hyper", "0.10.0", CrateDeps::default()),
+            release("hyper", "0.10.1", CrateDeps::default()),
+            release("hyper", "0.11.0", CrateDeps::default()),
+            release("log", "1.0.0", CrateDeps::default()),
+            release("log", "1.2.0", CrateDeps::default())
+        ]);
+
+        let analyzed = analyzer.finalize();
+        let untrusted = analyzed.main.get("untrusted").unwrap();
+        let hyper = analyzed.main.get("hyper").unwrap();
+
+        assert_eq!(untrusted.advisories[0].ignored, Some("not reachable".to_string()));
+        assert!(!untrusted.is_insecure());
+        // 0.10 to 0.11 is a breaking change, which the policy doesn't cover
+        assert!(hyper.is_outdated());
+        assert!(!analyzed.main.get("log").unwrap().is_outdated());
+        assert_eq!(analyzed.count_insecure(), 0);
+        assert_eq!(analyzed.count_outdated(), 1);
+    }
 }
@@ -1,57 +1,149 @@
 use std::collections::HashMap;
 
 use failure::Error;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use indexmap::IndexMap;
 
 use ::parsers::manifest::parse_manifest_toml;
-use ::models::crates::{CrateDep, CrateDeps, CrateName, CrateManifest};
+use ::parsers::lockfile::parse_lockfile_toml;
+use ::parsers::policy::{parse_manifest_policy, parse_policy_toml};
+use ::models::crates::{CrateDep, CrateDeps, CrateName, CrateManifest, CrateLockfile};
+use ::models::policy::Policy;
 
 pub struct ManifestCrawlerOutput {
-    pub crates: IndexMap<CrateName, CrateDeps>
+    pub crates: IndexMap<CrateName, CrateDeps>,
+    pub lockfile: Option<CrateLockfile>,
+    pub policy: Policy,
+    /// Why the repository's policy couldn't be parsed, in which case the default policy applies
+    pub policy_error: Option<String>
 }
 
 pub struct ManifestCrawlerStepOutput {
-    pub paths_of_interest: Vec<RelativePathBuf>
+    pub paths_of_interest: Vec<RelativePathBuf>,
+    pub directories_of_interest: Vec<RelativePathBuf>
+}
+
+impl ManifestCrawlerStepOutput {
+    fn new() -> ManifestCrawlerStepOutput {
+        ManifestCrawlerStepOutput {
+            paths_of_interest: vec![],
+            directories_of_interest: vec![]
+        }
+    }
 }
 
 pub struct ManifestCrawler {
     manifests: HashMap<RelativePathBuf, CrateManifest>,
-    leaf_crates: IndexMap<CrateName, CrateDeps>
+    leaf_crates: IndexMap<CrateName, CrateDeps>,
+    lockfile: Option<CrateLockfile>,
+    /// Policy from a `.deps.toml` file, which takes precedence over the root manifest
+    policy_file: Option<Policy>,
+    manifest_policy: Option<Policy>,
+    policy_error: Option<String>,
+    pending_globs: HashMap<RelativePathBuf, Vec<(String, String)>>,
+    excluded: Vec<RelativePathBuf>,
+    workspace_dependencies: Option<(RelativePathBuf, IndexMap<CrateName, CrateDep>)>
+}
+
+fn is_glob(segment: &str) -> bool {
+    segment.contains('*') || segment.contains('?')
+}
+
+/// Matches a single path segment against a pattern
+/// supporting the `*` and `?` wildcards
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(&b'*'), _) =>
+            glob_matches(&pattern[1..], name) || (!name.is_empty() && glob_matches(pattern, &name[1..])),
+        (Some(&b'?'), Some(_)) =>
+            glob_matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) =>
+            p == n && glob_matches(&pattern[1..], &name[1..]),
+        _ => false
+    }
 }
 
 impl ManifestCrawler {
     pub fn new() -> ManifestCrawler {
         ManifestCrawler {
             manifests: HashMap::new(),
-            leaf_crates: IndexMap::new()
+            leaf_crates: IndexMap::new(),
+            lockfile: None,
+            policy_file: None,
+            manifest_policy: None,
+            policy_error: None,
+            pending_globs: HashMap::new(),
+            excluded: vec![],
+            workspace_dependencies: None
+        }
+    }
+
+    pub fn step_lockfile(&mut self, raw_lockfile: String) -> Result<(), Error> {
+        self.lockfile = Some(parse_lockfile_toml(&raw_lockfile)?);
+        Ok(())
+    }
+
+    pub fn step_policy(&mut self, raw_policy: String) {
+        match parse_policy_toml(&raw_policy) {
+            Ok(policy) => self.policy_file = Some(policy),
+            Err(err) => self.record_policy_error(format!("failed to parse .deps.toml: {}", err))
+        }
+    }
+
+    fn record_policy_error(&mut self, message: String) {
+        if self.policy_error.is_none() {
+            self.policy_error = Some(message);
         }
     }
 
     pub fn step(&mut self, path: RelativePathBuf, raw_manifest: String) -> Result<ManifestCrawlerStepOutput, Error> {
         let manifest = parse_manifest_toml(&raw_manifest)?;
+        // the entry point is always crawled first
+        if self.manifests.is_empty() {
+            match parse_manifest_policy(&raw_manifest) {
+                Ok(policy) => self.manifest_policy = policy,
+                Err(err) => self.record_policy_error(format!("failed to parse the deps-rs metadata in Cargo.toml: {}", err))
+            }
+        }
         self.manifests.insert(path.clone(), manifest.clone());
 
-        let mut output = ManifestCrawlerStepOutput {
-            paths_of_interest: vec![]
-        };
+        let mut output = ManifestCrawlerStepOutput::new();
 
         match manifest {
             CrateManifest::Package(name, deps) => {
                 self.process_package(&path, name, deps, &mut output);
             },
-            CrateManifest::Workspace { members } => {
-                self.process_workspace(&path, &members, &mut output);
+            CrateManifest::Workspace { members, exclude, dependencies } => {
+                self.register_workspace_dependencies(&path, dependencies);
+                self.process_workspace(&path, &members, &exclude, &mut output);
             },
-            CrateManifest::Mixed { name, deps, members } => {
+            CrateManifest::Mixed { name, deps, members, exclude, dependencies } => {
+                self.register_workspace_dependencies(&path, dependencies);
                 self.process_package(&path, name, deps, &mut output);
-                self.process_workspace(&path, &members, &mut output);
+                self.process_workspace(&path, &members, &exclude, &mut output);
             }
         }
 
         Ok(output)
     }
 
+    pub fn step_directory(&mut self, path: RelativePathBuf, subdirectories: Vec<String>) -> ManifestCrawlerStepOutput {
+        let mut output = ManifestCrawlerStepOutput::new();
+
+        let patterns = self.pending_globs.remove(&path).unwrap_or_default();
+        for (segment_pattern, rest) in patterns {
+            for subdirectory in subdirectories.iter() {
+                if glob_matches(segment_pattern.as_bytes(), subdirectory.as_bytes()) {
+                    let candidate = format!("{}/{}/{}", path.as_str(), subdirectory, rest);
+                    self.register_glob_interest(&candidate, &mut output);
+                }
+            }
+        }
+
+        output
+    }
+
     fn register_interest(&mut self, base_path: &RelativePathBuf, path: &RelativePathBuf, output: &mut ManifestCrawlerStepOutput) {
         let full_path = base_path.join_normalized(path);
         if !self.manifests.contains_key(&full_path) {
@@ -59,8 +151,71 @@ impl ManifestCrawler {
         }
     }
 
-    fn process_package(&mut self, base_path: &RelativePathBuf, name: CrateName, deps: CrateDeps, output: &mut ManifestCrawlerStepOutput) {
-        for (_, dep) in deps.main.iter().chain(deps.dev.iter()).chain(deps.build.iter()) {
+    fn register_glob_interest(&mut self, pattern: &str, output: &mut ManifestCrawlerStepOutput) {
+        let segments = pattern.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+
+        match segments.iter().position(|segment| is_glob(segment)) {
+            None => {
+                let full_path = RelativePath::new(&segments.join("/")).to_relative_path_buf();
+                let is_excluded = self.excluded.iter().any(|excluded| {
+                    full_path == *excluded || full_path.as_str().starts_with(&format!("{}/", excluded.as_str()))
+                });
+                if !is_excluded && !self.manifests.contains_key(&full_path) {
+                    output.paths_of_interest.push(full_path);
+                }
+            },
+            Some(idx) => {
+                let directory = RelativePath::new(&segments[..idx].join("/")).to_relative_path_buf();
+                let pending = self.pending_globs.entry(directory.clone()).or_insert_with(Vec::new);
+                if pending.is_empty() {
+                    output.directories_of_interest.push(directory);
+                }
+                pending.push((segments[idx].to_string(), segments[idx + 1..].join("/")));
+            }
+        }
+    }
+
+    fn register_workspace_dependencies(&mut self, base_path: &RelativePathBuf, dependencies: IndexMap<CrateName, CrateDep>) {
+        // members inherit from the outermost workspace, which is crawled first
+        if self.workspace_dependencies.is_none() {
+            self.workspace_dependencies = Some((base_path.clone(), dependencies));
+        }
+    }
+
+    fn resolve_inherited(&self, base_path: &RelativePathBuf, deps: &mut IndexMap<CrateName, CrateDep>) {
+        if let Some((ref workspace_path, ref workspace_deps)) = self.workspace_dependencies {
+            for (name, dep) in deps.iter_mut() {
+                if *dep != CrateDep::Inherited {
+                    continue;
+                }
+                match workspace_deps.get(name) {
+                    Some(&CrateDep::Internal(ref path)) => {
+                        // rebase the path from the workspace root onto the member
+                        let depth = base_path.as_str().split('/').filter(|segment| !segment.is_empty()).count();
+                        let mut relative_path = "../".repeat(depth);
+                        relative_path.push_str(workspace_path.join_normalized(path).as_str());
+                        *dep = CrateDep::Internal(RelativePath::new(&relative_path).to_relative_path_buf());
+                    },
+                    Some(workspace_dep) => {
+                        *dep = workspace_dep.clone();
+                    },
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn process_package(&mut self, base_path: &RelativePathBuf, name: CrateName, mut deps: CrateDeps, output: &mut ManifestCrawlerStepOutput) {
+        self.resolve_inherited(base_path, &mut deps.main);
+        self.resolve_inherited(base_path, &mut deps.dev);
+        self.resolve_inherited(base_path, &mut deps.build);
+        for target_deps in deps.targets.values_mut() {
+            self.resolve_inherited(base_path, &mut target_deps.main);
+            self.resolve_inherited(base_path, &mut target_deps.dev);
+            self.resolve_inherited(base_path, &mut target_deps.build);
+        }
+
+        for (_, dep) in deps.iter() {
             if let &CrateDep::Internal(ref path) = dep {
                 self.register_interest(base_path, path, output);
             }
@@ -69,17 +224,33 @@ impl ManifestCrawler {
         self.leaf_crates.insert(name, deps);
     }
 
-    fn process_workspace(&mut self, base_path: &RelativePathBuf, members: &[RelativePathBuf], output: &mut ManifestCrawlerStepOutput) {
-        for mut path in members {
-            if !path.ends_with("*") {
+    fn process_workspace(&mut self, base_path: &RelativePathBuf, members: &[RelativePathBuf], exclude: &[RelativePathBuf], output: &mut ManifestCrawlerStepOutput) {
+        for path in exclude {
+            self.excluded.push(base_path.join_normalized(path));
+        }
+
+        for path in members {
+            if path.as_str().split('/').any(is_glob) {
+                let pattern = base_path.join_normalized(path);
+                self.register_glob_interest(pattern.as_str(), output);
+            } else {
                 self.register_interest(base_path, path, output);
             }
         }
     }
 
     pub fn finalize(self) -> ManifestCrawlerOutput {
+        // a policy that fails to parse doesn't acknowledge anything, rather than only part of it
+        let policy = match self.policy_error {
+            Some(_) => Policy::default(),
+            None => self.policy_file.or(self.manifest_policy).unwrap_or_default()
+        };
+
         ManifestCrawlerOutput {
-            crates: self.leaf_crates
+            crates: self.leaf_crates,
+            lockfile: self.lockfile,
+            policy,
+            policy_error: self.policy_error
         }
     }
 }
@@ -90,7 +261,16 @@ mod tests {
     use semver::VersionReq;
 
     use models::crates::CrateDep;
-    use super::ManifestCrawler;
+    use super::{ManifestCrawler, glob_matches};
+
+    #[test]
+    fn glob_segment_matching() {
+        assert!(glob_matches(b"*", b"foo"));
+        assert!(glob_matches(b"foo-*", b"foo-bar"));
+        assert!(glob_matches(b"f?o", b"foo"));
+        assert!(!glob_matches(b"foo-*", b"bar-foo"));
+        assert!(!glob_matches(b"f?o", b"fo"));
+    }
 
     #[test]
     fn simple_package_manifest() {
@@ -197,6 +377,35 @@ members = [
         let step_output = crawler.step("".into(), manifest.to_string()).unwrap();
         assert_eq!(step_output.paths_of_interest.len(), 1);
         assert_eq!(step_output.paths_of_interest[0].as_str(), "lib");
+        assert_eq!(step_output.directories_of_interest.len(), 1);
+        assert_eq!(step_output.directories_of_interest[0].as_str(), "tests");
+    }
+
+    #[test]
+    fn glob_workspace_manifest_with_exclude() {
+        let manifest = r#"
+[workspace]
+members = [
+  "crates/*",
+]
+exclude = [
+  "crates/experimental",
+]
+"#;
+        let mut crawler = ManifestCrawler::new();
+        let step_output = crawler.step("".into(), manifest.to_string()).unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 0);
+        assert_eq!(step_output.directories_of_interest.len(), 1);
+        assert_eq!(step_output.directories_of_interest[0].as_str(), "crates");
+        let step_output = crawler.step_directory("crates".into(), vec![
+            "core".to_string(),
+            "experimental".to_string(),
+            "cli".to_string()
+        ]);
+        assert_eq!(step_output.paths_of_interest.len(), 2);
+        assert_eq!(step_output.paths_of_interest[0].as_str(), "crates/core");
+        assert_eq!(step_output.paths_of_interest[1].as_str(), "crates/cli");
+        assert_eq!(step_output.directories_of_interest.len(), 0);
     }
 
     #[test]
@@ -244,4 +453,74 @@ features = ["use_std"]
         assert_eq!(output.crates["futures-cpupool"].dev.len(), 0);
         assert_eq!(output.crates["futures-cpupool"].build.len(), 0);
     }
+
+    #[test]
+    fn workspace_dependency_inheritance() {
+        let workspace_manifest = r#"
+[workspace]
+members = ["crates/app"]
+
+[workspace.dependencies]
+serde = "1.0"
+app-core = { path = "crates/core", version = "0.1" }
+"#;
+
+        let app_manifest = r#"
+[package]
+name = "app"
+
+[dependencies]
+serde = { workspace = true }
+app-core = { workspace = true }
+"#;
+
+        let mut crawler = ManifestCrawler::new();
+        let step_output = crawler.step("".into(), workspace_manifest.to_string()).unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 1);
+        assert_eq!(step_output.paths_of_interest[0].as_str(), "crates/app");
+        let step_output = crawler.step("crates/app".into(), app_manifest.to_string()).unwrap();
+        assert_eq!(step_output.paths_of_interest.len(), 1);
+        assert_eq!(step_output.paths_of_interest[0].as_str(), "crates/core");
+        let output = crawler.finalize();
+        assert_eq!(output.crates.len(), 1);
+        assert_eq!(output.crates["app"].main.get("serde").unwrap(),
+            &CrateDep::External(VersionReq::parse("1.0").unwrap()));
+    }
+
+    #[test]
+    fn policy_file_takes_precedence_over_manifest() {
+        let manifest = r#"
+[package]
+name = "simpleton"
+
+[package.metadata.deps-rs]
+allow-minor-outdated = true
+"#;
+        let mut crawler = ManifestCrawler::new();
+        crawler.step("Cargo.toml".into(), manifest.to_string()).unwrap();
+        assert!(crawler.manifest_policy.as_ref().unwrap().allow_minor_outdated);
+
+        crawler.step_policy("[[ignore-crate]]\nname = \"net2\"\n".to_string());
+        let output = crawler.finalize();
+        assert!(!output.policy.allow_minor_outdated);
+        assert_eq!(output.policy.crate_ignore_reason(&"net2".parse().unwrap()), Some("no reason given"));
+    }
+
+    #[test]
+    fn invalid_policy_falls_back_to_default() {
+        let manifest = r#"
+[package]
+name = "simpleton"
+
+[package.metadata.deps-rs]
+allow-minor-outdated = true
+"#;
+        let mut crawler = ManifestCrawler::new();
+        crawler.step("Cargo.toml".into(), manifest.to_string()).unwrap();
+        crawler.step_policy("[[ignore-crate]]\nreason = \"no name\"\n".to_string());
+        let output = crawler.finalize();
+        assert_eq!(output.crates.len(), 1);
+        assert!(!output.policy.allow_minor_outdated);
+        assert!(output.policy_error.unwrap().starts_with("failed to parse .deps.toml"));
+    }
 }
@@ -1,2 +1,19 @@
 pub mod crawler;
 pub mod analyzer;
+pub mod resolver;
+
+#[cfg(test)]
+mod testing {
+    use models::crates::{CrateDeps, CrateRelease};
+
+    /// Release of a crate that isn't yanked and has no features
+    pub fn release(name: &str, version: &str, deps: CrateDeps) -> CrateRelease {
+        CrateRelease {
+            name: name.parse().unwrap(),
+            version: version.parse().unwrap(),
+            deps,
+            features: Default::default(),
+            yanked: false
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use rustsec::db::AdvisoryDatabase;
+use semver::{Version, VersionReq};
+
+use ::models::crates::{AdvisoryMetadata, CrateDep, CrateDeps, CrateFeatures, CrateName, CrateRelease, DepFeatures, TransitiveDependency};
+use ::models::policy::Policy;
+use ::parsers::cfg::{TargetSpec, parse_target};
+
+/// Cfg values of the platforms whose target-specific dependencies are followed
+const PLATFORMS: &[(&str, &[(&str, Option<&str>)])] = &[
+    ("x86_64-unknown-linux-gnu", &[
+        ("unix", None), ("target_family", Some("unix")), ("target_os", Some("linux")),
+        ("target_arch", Some("x86_64")), ("target_env", Some("gnu")), ("target_vendor", Some("unknown")),
+        ("target_pointer_width", Some("64")), ("target_endian", Some("little"))
+    ]),
+    ("x86_64-pc-windows-msvc", &[
+        ("windows", None), ("target_family", Some("windows")), ("target_os", Some("windows")),
+        ("target_arch", Some("x86_64")), ("target_env", Some("msvc")), ("target_vendor", Some("pc")),
+        ("target_pointer_width", Some("64")), ("target_endian", Some("little"))
+    ]),
+    ("aarch64-apple-darwin", &[
+        ("unix", None), ("target_family", Some("unix")), ("target_os", Some("macos")),
+        ("target_arch", Some("aarch64")), ("target_env", Some("")), ("target_vendor", Some("apple")),
+        ("target_pointer_width", Some("64")), ("target_endian", Some("little"))
+    ])
+];
+
+/// Whether dependencies for the target are built on any of the common platforms.
+/// Targets that can't be parsed are followed, to err on the side of reporting.
+fn is_common_target(target: &str) -> bool {
+    match parse_target(target) {
+        Ok(TargetSpec::Triple(triple)) => PLATFORMS.iter().any(|&(platform, _)| platform == triple),
+        Ok(TargetSpec::Cfg(expr)) => PLATFORMS.iter().any(|&(_, cfg)| {
+            expr.matches(&|key: &str, value: Option<&str>| cfg.iter().any(|&(k, v)| k == key && v == value))
+        }),
+        Err(_) => true
+    }
+}
+
+/// Features enabled by a request, including the optional dependencies they enable.
+/// Entries like `dep/feature` are kept as they are, to pass the feature on to `dep`.
+fn enabled_features(features: &CrateFeatures, request: &DepFeatures) -> HashSet<String> {
+    let mut enabled = HashSet::new();
+    let mut queue = request.features.clone();
+    if request.default_features {
+        queue.push("default".to_string());
+    }
+
+    while let Some(feature) = queue.pop() {
+        if !enabled.insert(feature.clone()) {
+            continue;
+        }
+
+        if let Some(enables) = features.declared.get(&feature) {
+            for entry in enables {
+                if entry.starts_with("dep:") {
+                    queue.push(entry[4..].to_string());
+                } else if let Some(index) = entry.find('/') {
+                    // `dep?/feature` only applies if the dependency is enabled otherwise
+                    if !entry[..index].ends_with('?') {
+                        queue.push(entry[..index].to_string());
+                    }
+                    enabled.insert(entry.clone());
+                } else {
+                    queue.push(entry.clone());
+                }
+            }
+        }
+    }
+
+    enabled
+}
+
+pub struct TransitiveResolverStepOutput {
+    pub crates_of_interest: Vec<CrateName>
+}
+
+pub struct TransitiveResolver {
+    advisory_db: Option<Arc<AdvisoryDatabase>>,
+    advisory_metadata: Arc<HashMap<String, AdvisoryMetadata>>,
+    policy: Arc<Policy>,
+    releases: HashMap<CrateName, Vec<CrateRelease>>,
+    pending: HashMap<CrateName, Vec<(VersionReq, Vec<CrateName>, DepFeatures)>>,
+    /// Features enabled so far per resolved release
+    visited: HashMap<(CrateName, Version), HashSet<String>>,
+    flagged: Vec<TransitiveDependency>
+}
+
+impl TransitiveResolver {
+    pub fn new(advisory_db: Option<Arc<AdvisoryDatabase>>) -> TransitiveResolver {
+        TransitiveResolver {
+            advisory_db,
+            advisory_metadata: Arc::new(HashMap::new()),
+            policy: Arc::new(Policy::default()),
+            releases: HashMap::new(),
+            pending: HashMap::new(),
+            visited: HashMap::new(),
+            flagged: vec![]
+        }
+    }
+
+    pub fn set_advisory_metadata(&mut self, advisory_metadata: Arc<HashMap<String, AdvisoryMetadata>>) {
+        self.advisory_metadata = advisory_metadata;
+    }
+
+    pub fn set_policy(&mut self, policy: Arc<Policy>) {
+        self.policy = policy;
+    }
+
+    pub fn start(&mut self, deps: &CrateDeps) -> TransitiveResolverStepOutput {
+        let mut output = TransitiveResolverStepOutput {
+            crates_of_interest: vec![]
+        };
+
+        for (name, dep) in TransitiveResolver::runtime_deps(deps) {
+            if let Some((package, req)) = dep.external_package(name) {
+                self.register_interest(package.clone(), req.clone(), vec![], DepFeatures::default(), &mut output);
+            }
+        }
+
+        output
+    }
+
+    pub fn step(&mut self, name: CrateName, releases: Vec<CrateRelease>) -> TransitiveResolverStepOutput {
+        let mut output = TransitiveResolverStepOutput {
+            crates_of_interest: vec![]
+        };
+
+        let pending = self.pending.remove(&name).unwrap_or_default();
+        self.releases.insert(name.clone(), releases);
+
+        for (req, path, request) in pending {
+            self.resolve(&name, &req, path, &request, &mut output);
+        }
+
+        output
+    }
+
+    fn runtime_deps(deps: &CrateDeps) -> Vec<(&CrateName, &CrateDep)> {
+        let mut runtime_deps: Vec<_> = deps.main.iter().chain(deps.build.iter()).collect();
+        for (target, target_deps) in deps.targets.iter() {
+            if is_common_target(target) {
+                runtime_deps.extend(TransitiveResolver::runtime_deps(target_deps));
+            }
+        }
+        runtime_deps
+    }
+
+    fn register_interest(&mut self, name: CrateName, req: VersionReq, path: Vec<CrateName>, request: DepFeatures, output: &mut TransitiveResolverStepOutput) {
+        if self.releases.contains_key(&name) {
+            self.resolve(&name, &req, path, &request, output);
+        } else {
+            if !self.pending.contains_key(&name) {
+                output.crates_of_interest.push(name.clone());
+            }
+            self.pending.entry(name).or_insert_with(Vec::new).push((req, path, request));
+        }
+    }
+
+    fn select_release<'a>(releases: &'a [CrateRelease], req: &VersionReq) -> Option<&'a CrateRelease> {
+        let matching = releases.iter().filter(|release| req.matches(&release.version));
+
+        matching.clone()
+            .filter(|release| !release.yanked)
+            .max_by(|r1, r2| r1.version.cmp(&r2.version))
+            .or_else(|| matching.max_by(|r1, r2| r1.version.cmp(&r2.version)))
+    }
+
+    fn resolve(&mut self, name: &CrateName, req: &VersionReq, path: Vec<CrateName>, request: &DepFeatures, output: &mut TransitiveResolverStepOutput) {
+        let release = match self.releases.get(name).and_then(|releases| TransitiveResolver::select_release(releases, req)) {
+            Some(release) => release.clone(),
+            None => return
+        };
+
+        // a release is walked again only if a dependent enables more of its features
+        let requested = enabled_features(&release.features, request);
+        let (is_first_visit, enabled) = match self.visited.get_mut(&(name.clone(), release.version.clone())) {
+            Some(known) => {
+                if requested.is_subset(known) {
+                    return;
+                }
+                known.extend(requested);
+                (false, known.clone())
+            },
+            None => (true, requested)
+        };
+        if is_first_visit {
+            self.visited.insert((name.clone(), release.version.clone()), enabled.clone());
+        }
+
+        let advisory_kind = {
+            let advisory_metadata = &self.advisory_metadata;
+            let policy = &self.policy;
+            self.advisory_db.as_ref().and_then(|db| {
+                db.find_vulns_for_crate(name.as_ref(), &release.version).into_iter()
+                    .filter(|advisory| policy.advisory_ignore_reason(&advisory.id).is_none())
+                    .map(|advisory| advisory_metadata.get(&advisory.id).map(|metadata| metadata.kind).unwrap_or_default())
+                    .max()
+            })
+        };
+        let is_ignored = self.policy.crate_ignore_reason(name).is_some();
+
+        if is_first_visit && !path.is_empty() && !is_ignored && (advisory_kind.is_some() || release.yanked) {
+            self.flagged.push(TransitiveDependency {
+                name: name.clone(),
+                version: release.version.clone(),
+                path: path.clone(),
+                advisory_kind,
+                yanked: release.yanked
+            });
+        }
+
+        let mut child_path = path;
+        child_path.push(name.clone());
+
+        for (dep_name, dep) in TransitiveResolver::runtime_deps(&release.deps) {
+            if release.features.optional.contains(dep_name) && !enabled.contains(dep_name.as_ref()) {
+                continue;
+            }
+
+            let mut dep_request = release.features.requested.get(dep_name).cloned().unwrap_or_default();
+            let prefixes = [format!("{}/", dep_name.as_ref()), format!("{}?/", dep_name.as_ref())];
+            for feature in enabled.iter() {
+                for prefix in prefixes.iter() {
+                    if feature.starts_with(prefix.as_str()) {
+                        dep_request.features.push(feature[prefix.len()..].to_string());
+                    }
+                }
+            }
+
+            if let Some((dep_package, dep_req)) = dep.external_package(dep_name) {
+                self.register_interest(dep_package.clone(), dep_req.clone(), child_path.clone(), dep_request, output);
+            }
+        }
+    }
+
+    /// Flagged dependencies in a stable order, independent of the order releases were fetched in
+    pub fn finalize(mut self) -> Vec<TransitiveDependency> {
+        self.flagged.sort_by(|a, b| (&a.name, &a.version, &a.path).cmp(&(&b.name, &b.version, &b.path)));
+        self.flagged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustsec::db::AdvisoryDatabase;
+
+    use models::crates::{CrateDep, CrateDeps, CrateFeatures, CrateRelease};
+    use engine::machines::testing::release;
+    use super::TransitiveResolver;
+
+    const ADVISORIES: &str = r#"
+[[advisory]]
+id = "RUSTSEC-2018-0001"
+package = "untrusted"
+patched_versions = [">= 0.6.2"]
+date = "2018-06-21"
+url = "https://github.com/briansmith/untrusted/pull/20"
+title = "An integer underflow could lead to panic"
+description = "An integer underflow could lead to panic."
+"#;
+
+    #[test]
+    fn walks_dependency_tree() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.11.0".parse().unwrap()));
+
+        let mut hyper_deps = CrateDeps::default();
+        hyper_deps.main.insert("tokio-core".parse().unwrap(), CrateDep::External("^0.1.0".parse().unwrap()));
+
+        let mut resolver = TransitiveResolver::new(None);
+        let output = resolver.start(&deps);
+        assert_eq!(output.crates_of_interest.len(), 1);
+        assert_eq!(output.crates_of_interest[0].as_ref(), "hyper");
+
+        let output = resolver.step("hyper".parse().unwrap(), vec![
+            release("hyper", "0.11.0", hyper_deps)
+        ]);
+        assert_eq!(output.crates_of_interest.len(), 1);
+        assert_eq!(output.crates_of_interest[0].as_ref(), "tokio-core");
+
+        let output = resolver.step("tokio-core".parse().unwrap(), vec![
+            release("tokio-core", "0.1.0", CrateDeps::default())
+        ]);
+        assert_eq!(output.crates_of_interest.len(), 0);
+
+        assert_eq!(resolver.finalize().len(), 0);
+    }
+
+    #[test]
+    fn flags_yanked_transitive_releases() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.11.0".parse().unwrap()));
+
+        let mut hyper_deps = CrateDeps::default();
+        hyper_deps.main.insert("tokio-core".parse().unwrap(), CrateDep::External("=0.1.1".parse().unwrap()));
+
+        let mut resolver = TransitiveResolver::new(None);
+        resolver.start(&deps);
+        resolver.step("hyper".parse().unwrap(), vec![
+            release("hyper", "0.11.0", hyper_deps)
+        ]);
+        resolver.step("tokio-core".parse().unwrap(), vec![
+            release("tokio-core", "0.1.0", CrateDeps::default()),
+            CrateRelease { yanked: true, ..release("tokio-core", "0.1.1", CrateDeps::default()) }
+        ]);
+
+        let flagged = resolver.finalize();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].name.as_ref(), "tokio-core");
+        assert_eq!(flagged[0].version, "0.1.1".parse().unwrap());
+        assert_eq!(flagged[0].path.len(), 1);
+        assert_eq!(flagged[0].path[0].as_ref(), "hyper");
+        assert!(flagged[0].yanked);
+    }
+
+    #[test]
+    fn skips_disabled_optional_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.11.0".parse().unwrap()));
+
+        let mut hyper_deps = CrateDeps::default();
+        hyper_deps.main.insert("tokio-core".parse().unwrap(), CrateDep::External("^0.1.0".parse().unwrap()));
+        hyper_deps.main.insert("native-tls".parse().unwrap(), CrateDep::External("^0.1.0".parse().unwrap()));
+        hyper_deps.main.insert("serde".parse().unwrap(), CrateDep::External("^1.0.0".parse().unwrap()));
+        let mut hyper_features = CrateFeatures::default();
+        hyper_features.declared.insert("default".to_string(), vec!["tls".to_string()]);
+        hyper_features.declared.insert("tls".to_string(), vec!["dep:native-tls".to_string()]);
+        hyper_features.optional.push("native-tls".parse().unwrap());
+        hyper_features.optional.push("serde".parse().unwrap());
+
+        let mut resolver = TransitiveResolver::new(None);
+        resolver.start(&deps);
+        let output = resolver.step("hyper".parse().unwrap(), vec![
+            CrateRelease { features: hyper_features, ..release("hyper", "0.11.0", hyper_deps) }
+        ]);
+        let names: Vec<_> = output.crates_of_interest.iter().map(|name| name.as_ref()).collect();
+        assert_eq!(names, vec!["tokio-core", "native-tls"]);
+    }
+
+    #[test]
+    fn skips_uncommon_target_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.11.0".parse().unwrap()));
+
+        let mut hyper_deps = CrateDeps::default();
+        let mut unix_deps = CrateDeps::default();
+        unix_deps.main.insert("libc".parse().unwrap(), CrateDep::External("^0.2.0".parse().unwrap()));
+        hyper_deps.targets.insert("cfg(unix)".to_string(), unix_deps);
+        let mut wasm_deps = CrateDeps::default();
+        wasm_deps.main.insert("wasm-bindgen".parse().unwrap(), CrateDep::External("^0.2.0".parse().unwrap()));
+        hyper_deps.targets.insert("cfg(target_arch = \"wasm32\")".to_string(), wasm_deps);
+        let mut redox_deps = CrateDeps::default();
+        redox_deps.main.insert("redox_syscall".parse().unwrap(), CrateDep::External("^0.1.0".parse().unwrap()));
+        hyper_deps.targets.insert("x86_64-unknown-redox".to_string(), redox_deps);
+
+        let mut resolver = TransitiveResolver::new(None);
+        resolver.start(&deps);
+        let output = resolver.step("hyper".parse().unwrap(), vec![
+            release("hyper", "0.11.0", hyper_deps)
+        ]);
+        assert_eq!(output.crates_of_interest.len(), 1);
+        assert_eq!(output.crates_of_interest[0].as_ref(), "libc");
+    }
+
+    #[test]
+    fn sorts_flagged_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("hyper".parse().unwrap(), CrateDep::External("^0.11.0".parse().unwrap()));
+
+        let mut hyper_deps = CrateDeps::default();
+        hyper_deps.main.insert("tokio-core".parse().unwrap(), CrateDep::External("^0.1.0".parse().unwrap()));
+        hyper_deps.main.insert("base64".parse().unwrap(), CrateDep::External("^0.9.0".parse().unwrap()));
+
+        let mut resolver = TransitiveResolver::new(None);
+        resolver.start(&deps);
+        resolver.step("hyper".parse().unwrap(), vec![
+            release("hyper", "0.11.0", hyper_deps)
+        ]);
+        resolver.step("tokio-core".parse().unwrap(), vec![
+            CrateRelease { yanked: true, ..release("tokio-core", "0.1.0", CrateDeps::default()) }
+        ]);
+        resolver.step("base64".parse().unwrap(), vec![
+            CrateRelease { yanked: true, ..release("base64", "0.9.0", CrateDeps::default()) }
+        ]);
+
+        let names: Vec<_> = resolver.finalize().into_iter().map(|dep| dep.name.as_ref().to_string()).collect();
+        assert_eq!(names, vec!["base64", "tokio-core"]);
+    }
+
+    #[test]
+    fn flags_insecure_build_dependencies() {
+        let mut deps = CrateDeps::default();
+        deps.main.insert("ring".parse().unwrap(), CrateDep::External("^0.12.0".parse().unwrap()));
+
+        let mut ring_deps = CrateDeps::default();
+        ring_deps.build.insert("untrusted".parse().unwrap(), CrateDep::External("=0.6.1".parse().unwrap()));
+
+        let advisory_db = AdvisoryDatabase::from_toml(ADVISORIES).unwrap();
+        let mut resolver = TransitiveResolver::new(Some(Arc::new(advisory_db)));
+        resolver.start(&deps);
+        let output = resolver.step("ring".parse().unwrap(), vec![
+            release("ring", "0.12.1", ring_deps)
+        ]);
+        assert_eq!(output.crates_of_interest.len(), 1);
+        assert_eq!(output.crates_of_interest[0].as_ref(), "untrusted");
+        resolver.step("untrusted".parse().unwrap(), vec![
+            release("untrusted", "0.6.1", CrateDeps::default())
+        ]);
+
+        let flagged = resolver.finalize();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].name.as_ref(), "untrusted");
+        assert!(flagged[0].is_insecure());
+        assert_eq!(flagged[0].path[0].as_ref(), "ring");
+    }
+}
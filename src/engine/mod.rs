@@ -1,68 +1,115 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use cadence::prelude::*;
+use chrono::{DateTime, Utc};
 use cadence::{MetricSink, NopMetricSink, StatsdClient};
 use failure::Error;
-use futures::{Future, future};
+use futures::{Future, IntoFuture, future};
 use futures::future::join_all;
+use futures::sync::oneshot;
+use futures_cpupool::CpuPool;
 use hyper::Client;
 use hyper::client::HttpConnector;
 use hyper_tls::HttpsConnector;
 use relative_path::{RelativePath, RelativePathBuf};
-use rustsec::db::AdvisoryDatabase;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use slog::Logger;
+use tokio_core::reactor::Timeout;
 use tokio_service::Service;
 
 mod machines;
 mod futures;
 
 use ::utils::cache::Cache;
+use ::utils::store::{Persisted, Store};
+use ::utils::throttle::Throttle;
 
+use ::models::policy::Policy;
 use ::models::repo::{Repository, RepoPath};
-use ::models::crates::{CrateName, CratePath, CrateRelease, AnalyzedDependencies};
+use ::models::crates::{AdvisoryKind, CrateName, CratePath, CrateRelease, AnalyzedDependencies};
 
-use ::interactors::crates::{QueryCrate, GetPopularCrates};
-use ::interactors::RetrieveFileAtPath;
+use ::interactors::crates::{QueryCrate, QueryCrateVersions, GetPopularCrates};
+use ::interactors::{RetrieveFileAtPath, ListDirectoryAtPath, UpstreamError, UpstreamErrorKind};
+use ::interactors::local::{RetrieveLocalFile, ListLocalDirectory};
 use ::interactors::github::GetPopularRepos;
-use ::interactors::rustsec::FetchAdvisoryDatabase;
+use ::interactors::rustsec::{AdvisoryDatabaseResponse, FetchAdvisoryDatabase};
 
 use self::futures::AnalyzeDependenciesFuture;
 use self::futures::CrawlManifestFuture;
 
 type HttpClient = Client<HttpsConnector<HttpConnector>>;
 
+/// Release dates are cached per newest release, so they only need to be fetched
+/// again once a new version is published
+const RELEASE_DATES_LIFETIME_SECS: u64 = 30 * 24 * 3600;
+/// How long an analysis waits for release dates from the throttled crates.io API
+const RELEASE_DATES_TIMEOUT_SECS: u64 = 5;
+
 #[derive(Clone, Debug)]
 pub struct Engine {
     client: HttpClient,
     logger: Logger,
     metrics: StatsdClient,
 
-    query_crate: Arc<Cache<QueryCrate<HttpClient>>>,
+    query_crate: Arc<Cache<Persisted<QueryCrate<HttpClient>>>>,
+    query_crate_versions: Arc<Cache<Persisted<Throttle<QueryCrateVersions<HttpClient>>>>>,
     get_popular_crates: Arc<Cache<GetPopularCrates<HttpClient>>>,
     get_popular_repos: Arc<Cache<GetPopularRepos<HttpClient>>>,
-    retrieve_file_at_path: Arc<RetrieveFileAtPath<HttpClient>>,
-    fetch_advisory_db: Arc<Cache<FetchAdvisoryDatabase<HttpClient>>>
+    retrieve_file_at_path: Arc<Cache<Persisted<RetrieveFileAtPath<HttpClient>>>>,
+    list_directory_at_path: Arc<Cache<Persisted<ListDirectoryAtPath<HttpClient>>>>,
+    retrieve_local_file: Arc<RetrieveLocalFile>,
+    list_local_directory: Arc<ListLocalDirectory>,
+    fetch_advisory_db: Arc<Cache<Persisted<FetchAdvisoryDatabase<HttpClient>>>>
 }
 
 impl Engine {
-    pub fn new(client: Client<HttpsConnector<HttpConnector>>, logger: Logger) -> Engine {
+    pub fn new(client: Client<HttpsConnector<HttpConnector>>, logger: Logger, store: Option<Arc<Store>>) -> Engine {
         let metrics = StatsdClient::from_sink("engine", NopMetricSink);
-
-        let query_crate = Cache::new(QueryCrate(client.clone()), Duration::from_secs(300), 500);
+        let handle = client.handle().clone();
+        let pool = CpuPool::new(2);
+
+        let query_crate = Cache::new(
+            Persisted::new(QueryCrate(client.clone()), store.clone(), pool.clone(), logger.clone(), "query_crate", Duration::from_secs(300)),
+            Duration::from_secs(300), 500)
+            .revalidate_in_background(handle.clone(), Duration::from_secs(3600))
+            .cache_not_found(Duration::from_secs(600));
+        let query_crate_versions = Cache::new(
+            Persisted::new(
+                Throttle::new(QueryCrateVersions(client.clone()), handle.clone(), Duration::from_secs(1)),
+                store.clone(), pool.clone(), logger.clone(), "query_crate_versions", Duration::from_secs(RELEASE_DATES_LIFETIME_SECS)),
+            Duration::from_secs(RELEASE_DATES_LIFETIME_SECS), 500)
+            .cache_not_found(Duration::from_secs(600));
         let get_popular_crates = Cache::new(GetPopularCrates(client.clone()), Duration::from_secs(10), 1);
         let get_popular_repos = Cache::new(GetPopularRepos(client.clone()), Duration::from_secs(10), 1);
-        let fetch_advisory_db =  Cache::new(FetchAdvisoryDatabase(client.clone()), Duration::from_secs(300), 1);
+        let retrieve_file_at_path = Cache::new(
+            Persisted::new(RetrieveFileAtPath(client.clone()), store.clone(), pool.clone(), logger.clone(), "retrieve_file", Duration::from_secs(60)),
+            Duration::from_secs(60), 500)
+            .cache_not_found(Duration::from_secs(600));
+        let list_directory_at_path = Cache::new(
+            Persisted::new(ListDirectoryAtPath(client.clone()), store.clone(), pool.clone(), logger.clone(), "list_directory", Duration::from_secs(60)),
+            Duration::from_secs(60), 500)
+            .cache_not_found(Duration::from_secs(600));
+        let fetch_advisory_db = Cache::new(
+            Persisted::new(FetchAdvisoryDatabase(client.clone()), store, pool, logger.clone(), "fetch_advisory_db", Duration::from_secs(300)),
+            Duration::from_secs(300), 1)
+            .revalidate_in_background(handle, Duration::from_secs(3600));
 
         Engine {
             client: client.clone(), logger, metrics,
 
             query_crate: Arc::new(query_crate),
+            query_crate_versions: Arc::new(query_crate_versions),
             get_popular_crates: Arc::new(get_popular_crates),
             get_popular_repos: Arc::new(get_popular_repos),
-            retrieve_file_at_path: Arc::new(RetrieveFileAtPath(client)),
+            retrieve_file_at_path: Arc::new(retrieve_file_at_path),
+            list_directory_at_path: Arc::new(list_directory_at_path),
+            retrieve_local_file: Arc::new(RetrieveLocalFile),
+            list_local_directory: Arc::new(ListLocalDirectory),
             fetch_advisory_db: Arc::new(fetch_advisory_db)
         }
     }
@@ -72,8 +119,19 @@ impl Engine {
     }
 }
 
+/// Where the manifests of a project are read from
+#[derive(Clone, Debug)]
+pub enum ManifestSource {
+    Repo(RepoPath),
+    Local(PathBuf)
+}
+
 pub struct AnalyzeDependenciesOutcome {
     pub crates: Vec<(CrateName, AnalyzedDependencies)>,
+    /// Policy of the analyzed repository, empty for crates
+    pub policy: Arc<Policy>,
+    /// Why the repository's policy couldn't be parsed, if it couldn't
+    pub policy_error: Option<String>,
     pub duration: Duration
 }
 
@@ -82,8 +140,36 @@ impl AnalyzeDependenciesOutcome {
         self.crates.iter().any(|&(_, ref deps)| deps.any_outdated())
     }
 
+    pub fn any_yanked_only(&self) -> bool {
+        self.crates.iter().any(|&(_, ref deps)| deps.any_yanked_only())
+    }
+
+    pub fn any_unsatisfiable(&self) -> bool {
+        self.crates.iter().any(|&(_, ref deps)| deps.any_unsatisfiable())
+    }
+
     pub fn any_insecure(&self) -> bool {
-        self.crates.iter().any(|&(_, ref deps)| deps.count_insecure() > 0)
+        self.crates.iter().any(|&(_, ref deps)| deps.count_insecure() > 0 || deps.count_transitive_insecure() > 0)
+    }
+
+    pub fn advisory_kind(&self) -> Option<AdvisoryKind> {
+        self.crates.iter().filter_map(|&(_, ref deps)| deps.advisory_kind()).max()
+    }
+
+    /// Fingerprint of the analysis results, leaving out timing information.
+    /// Crates are hashed in sorted order, since workspace members are
+    /// discovered in whatever order their manifests arrive.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let mut crates: Vec<_> = self.crates.iter().collect();
+        crates.sort_by(|a, b| a.0.cmp(&b.0));
+        for &&(ref name, ref deps) in crates.iter() {
+            name.hash(&mut hasher);
+            hash_analyzed_deps(deps, &mut hasher);
+        }
+        self.policy.hash(&mut hasher);
+        self.policy_error.hash(&mut hasher);
+        hasher.finish()
     }
 
     pub fn outdated_ratio(&self) -> (usize, usize) {
@@ -91,6 +177,33 @@ impl AnalyzeDependenciesOutcome {
             (outdated + deps.count_outdated(), total + deps.count_total())
         })
     }
+
+    pub fn libyears(&self) -> f64 {
+        self.crates.iter().map(|&(_, ref deps)| deps.libyears()).sum()
+    }
+
+    /// Like `outdated_ratio`, but only counting dependencies outdated by a breaking release
+    pub fn major_outdated_ratio(&self) -> (usize, usize) {
+        self.crates.iter().fold((0, 0), |(outdated, total), &(_, ref deps)| {
+            (outdated + deps.count_major_outdated(), total + deps.count_total())
+        })
+    }
+}
+
+fn hash_analyzed_deps<H: Hasher>(deps: &AnalyzedDependencies, hasher: &mut H) {
+    // the debug representation covers every analyzed field
+    format!("{:?}", deps.main).hash(hasher);
+    format!("{:?}", deps.dev).hash(hasher);
+    format!("{:?}", deps.build).hash(hasher);
+    for (target, target_deps) in deps.targets.iter() {
+        target.hash(hasher);
+        hash_analyzed_deps(target_deps, hasher);
+    }
+    // transitive dependencies are collected in the order their releases were fetched
+    let mut transitive: Vec<_> = deps.transitive.iter().map(|dep| format!("{:?}", dep)).collect();
+    transitive.sort();
+    transitive.hash(hasher);
+    deps.transitive_truncated.hash(hasher);
 }
 
 impl Engine {
@@ -117,30 +230,54 @@ impl Engine {
     {
         let start = Instant::now();
 
+        let engine = self.clone();
+        self.analyze_manifest_source(ManifestSource::Repo(repo_path.clone())).and_then(move |(crates, policy, policy_error)| {
+            let duration = start.elapsed();
+            engine.metrics.time_duration_with_tags("analyze_duration", duration)
+                .with_tag("repo_site", repo_path.site.as_ref())
+                .with_tag("repo_qual", repo_path.qual.as_ref())
+                .with_tag("repo_name", repo_path.name.as_ref())
+                .with_tag("repo_ref", repo_path.revision())
+                .send()?;
+
+            Ok(AnalyzeDependenciesOutcome {
+                crates, policy, policy_error, duration
+            })
+        })
+    }
+
+    pub fn analyze_local_dependencies(&self, root: PathBuf) ->
+        impl Future<Item=AnalyzeDependenciesOutcome, Error=Error>
+    {
+        let start = Instant::now();
+
+        self.analyze_manifest_source(ManifestSource::Local(root)).map(move |(crates, policy, policy_error)| {
+            AnalyzeDependenciesOutcome {
+                crates, policy, policy_error,
+                duration: start.elapsed()
+            }
+        })
+    }
+
+    fn analyze_manifest_source(&self, source: ManifestSource) ->
+        impl Future<Item=(Vec<(CrateName, AnalyzedDependencies)>, Arc<Policy>, Option<String>), Error=Error>
+    {
         let entry_point = RelativePath::new("/").to_relative_path_buf();
-        let manifest_future = CrawlManifestFuture::new(self, repo_path.clone(), entry_point);
+        let manifest_future = CrawlManifestFuture::new(self, source, entry_point);
 
         let engine = self.clone();
         manifest_future.and_then(move |manifest_output| {
-            let engine_for_analyze = engine.clone();
+            let lockfile = manifest_output.lockfile.map(Arc::new);
+            let policy = Arc::new(manifest_output.policy);
+            let policy_error = manifest_output.policy_error;
+            let crate_policy = policy.clone();
             let futures = manifest_output.crates.into_iter().map(move |(crate_name, deps)| {
-                let analyzed_deps_future = AnalyzeDependenciesFuture::new(engine_for_analyze.clone(), deps);
+                let analyzed_deps_future = AnalyzeDependenciesFuture::new(engine.clone(), deps, lockfile.clone(), crate_policy.clone());
 
                 analyzed_deps_future.map(move |analyzed_deps| (crate_name, analyzed_deps))
             });
 
-            join_all(futures).and_then(move |crates| {
-                let duration = start.elapsed();
-                engine.metrics.time_duration_with_tags("analyze_duration", duration)
-                    .with_tag("repo_site", repo_path.site.as_ref())
-                    .with_tag("repo_qual", repo_path.qual.as_ref())
-                    .with_tag("repo_name", repo_path.name.as_ref())
-                    .send()?;
-
-                Ok(AnalyzeDependenciesOutcome {
-                    crates, duration
-                })
-            })
+            join_all(futures).map(move |crates| (crates, policy, policy_error))
         })
     }
 
@@ -154,16 +291,21 @@ impl Engine {
         let engine = self.clone();
         query_future.and_then(move |query_response| {
             match query_response.releases.iter().find(|release| release.version == crate_path.version) {
-                None => future::Either::A(future::err(format_err!("could not find crate release with version {}", crate_path.version))),
+                None => {
+                    let message = format!("could not find crate release with version {}", crate_path.version);
+                    future::Either::A(future::err(UpstreamError::new(UpstreamErrorKind::NotFound, message).into()))
+                },
                 Some(release) => {
-                    let analyzed_deps_future = AnalyzeDependenciesFuture::new(engine.clone(), release.deps.clone());
+                    let analyzed_deps_future = AnalyzeDependenciesFuture::new(engine.clone(), release.deps.clone(), None, Arc::new(Policy::default()));
 
                     future::Either::B(analyzed_deps_future.map(move |analyzed_deps| {
                         let crates = vec![(crate_path.name, analyzed_deps)].into_iter().collect();
                         let duration = start.elapsed();
 
                         AnalyzeDependenciesOutcome {
-                            crates, duration
+                            crates, duration,
+                            policy: Arc::new(Policy::default()),
+                            policy_error: None
                         }
                     }))
                 }
@@ -193,17 +335,87 @@ impl Engine {
         })
     }
 
-    fn retrieve_manifest_at_path(&self, repo_path: &RepoPath, path: &RelativePathBuf) ->
+    /// Publication dates per version, or `None` if crates.io couldn't be queried in time.
+    /// Dates that arrive too late are still cached for the next analysis.
+    fn fetch_release_dates<I: IntoIterator<Item=CrateName>>(&self, names: I) ->
+        impl Iterator<Item=impl Future<Item=(CrateName, Option<HashMap<Version, DateTime<Utc>>>), Error=Error>>
+    {
+        let engine = self.clone();
+        names.into_iter().map(move |name| {
+            let query_crate_versions = engine.query_crate_versions.clone();
+            let deadline = Timeout::new(Duration::from_secs(RELEASE_DATES_TIMEOUT_SECS), engine.client.handle());
+            let package = name.clone();
+            let dates_future = engine.query_crate.call(name.clone()).from_err::<Error>().and_then(move |query_response| {
+                let newest = query_response.releases.iter().map(|release| &release.version).max().cloned();
+                match newest {
+                    Some(version) => future::Either::A(query_crate_versions.call(CratePath { name: package, version })
+                        .from_err().map(|resp| Some(resp.published.clone()))),
+                    None => future::Either::B(future::ok(None))
+                }
+            });
+
+            // driven on the event loop, so that dates arriving after the deadline still get cached
+            let (sender, receiver) = oneshot::channel();
+            engine.client.handle().spawn(dates_future.then(move |result| {
+                let _ = sender.send(result.ok().and_then(|dates| dates));
+                Ok(())
+            }));
+
+            let deadline_future = deadline.into_future().flatten().map(|()| None).from_err();
+            receiver.from_err::<Error>().select(deadline_future)
+                .then(move |result| Ok((name, result.ok().and_then(|(dates, _)| dates))) as Result<_, Error>)
+        })
+    }
+
+    fn retrieve_manifest_at_path(&self, source: &ManifestSource, path: &RelativePathBuf) ->
         impl Future<Item=String, Error=Error>
     {
         let manifest_path = path.join(RelativePath::new("Cargo.toml"));
-        self.retrieve_file_at_path.call((repo_path.clone(), manifest_path))
+        self.retrieve_file_from_source(source, manifest_path)
+    }
+
+    fn retrieve_lockfile_at_path(&self, source: &ManifestSource, path: &RelativePathBuf) ->
+        impl Future<Item=String, Error=Error>
+    {
+        let lockfile_path = path.join(RelativePath::new("Cargo.lock"));
+        self.retrieve_file_from_source(source, lockfile_path)
+    }
+
+    fn retrieve_policy_at_path(&self, source: &ManifestSource, path: &RelativePathBuf) ->
+        impl Future<Item=String, Error=Error>
+    {
+        let policy_path = path.join(RelativePath::new(".deps.toml"));
+        self.retrieve_file_from_source(source, policy_path)
+    }
+
+    fn retrieve_file_from_source(&self, source: &ManifestSource, path: RelativePathBuf) ->
+        impl Future<Item=String, Error=Error>
+    {
+        match *source {
+            ManifestSource::Repo(ref repo_path) =>
+                future::Either::A(self.retrieve_file_at_path.call((repo_path.clone(), path))
+                    .from_err().map(|contents| contents.clone())),
+            ManifestSource::Local(ref root) =>
+                future::Either::B(self.retrieve_local_file.call((root.clone(), path)))
+        }
+    }
+
+    fn list_subdirectories_at_path(&self, source: &ManifestSource, path: &RelativePathBuf) ->
+        impl Future<Item=Vec<String>, Error=Error>
+    {
+        match *source {
+            ManifestSource::Repo(ref repo_path) =>
+                future::Either::A(self.list_directory_at_path.call((repo_path.clone(), path.clone()))
+                    .from_err().map(|names| names.clone())),
+            ManifestSource::Local(ref root) =>
+                future::Either::B(self.list_local_directory.call((root.clone(), path.clone())))
+        }
     }
 
     fn fetch_advisory_db(&self) ->
-        impl Future<Item=Arc<AdvisoryDatabase>, Error=Error>
+        impl Future<Item=AdvisoryDatabaseResponse, Error=Error>
     {
-        self.fetch_advisory_db.call(()).from_err().map(|db| db.clone())
+        self.fetch_advisory_db.call(()).from_err().map(|response| (*response).clone())
     }
 }
 
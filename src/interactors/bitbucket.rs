@@ -1,18 +1,60 @@
 use failure::Error;
 use hyper::Uri;
 use relative_path::RelativePathBuf;
+use serde_json;
 
 use ::models::repo::RepoPath;
 
 const BITBUCKET_USER_CONTENT_BASE_URI: &'static str = "https://bitbucket.org";
+const BITBUCKET_API_BASE_URI: &'static str = "https://api.bitbucket.org/2.0";
 
 pub fn get_manifest_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
     let path_str: &str = path.as_ref();
-    Ok(format!("{}/{}/{}/raw/HEAD/{}",
+    Ok(format!("{}/{}/{}/raw/{}/{}",
         BITBUCKET_USER_CONTENT_BASE_URI,
         repo_path.qual.as_ref(),
         repo_path.name.as_ref(),
+        repo_path.revision(),
         path_str
     ).parse::<Uri>()?)
 }
 
+pub fn get_tree_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
+    let path_str: &str = path.as_ref();
+    Ok(format!("{}/repositories/{}/{}/src/{}/{}?pagelen=100",
+        BITBUCKET_API_BASE_URI,
+        repo_path.qual.as_ref(),
+        repo_path.name.as_ref(),
+        repo_path.revision(),
+        path_str.trim_matches('/')
+    ).parse::<Uri>()?)
+}
+
+#[derive(Deserialize)]
+struct BitbucketSrcEntry {
+    path: String,
+    #[serde(rename = "type")]
+    entry_type: String
+}
+
+#[derive(Deserialize)]
+struct BitbucketSrcResponse {
+    values: Vec<BitbucketSrcEntry>,
+    /// Link to the next page of the listing, if there is one
+    #[serde(default)]
+    next: Option<String>
+}
+
+/// Parses one page of a listing, along with the URI of the next page
+pub fn parse_tree_response(body: &[u8]) -> Result<(Vec<String>, Option<Uri>), Error> {
+    let response = serde_json::from_slice::<BitbucketSrcResponse>(body)?;
+    let next = match response.next {
+        Some(next) => Some(next.parse::<Uri>()?),
+        None => None
+    };
+    let names = response.values.into_iter()
+        .filter(|entry| entry.entry_type == "commit_directory")
+        .filter_map(|entry| entry.path.rsplit('/').next().map(|name| name.to_string()))
+        .collect();
+    Ok((names, next))
+}
@@ -1,23 +1,45 @@
+use std::collections::HashMap;
 use std::str;
 
+use chrono::{DateTime, Utc};
 use failure::Error;
 use futures::{Future, Stream, IntoFuture, future};
+use indexmap::IndexMap;
 use hyper::{Error as HyperError, Method, Request, Response, Uri};
+use hyper::header::UserAgent;
 use tokio_service::Service;
 use semver::{Version, VersionReq};
 use serde_json;
 
-use ::models::crates::{CrateName, CrateRelease, CrateDeps, CrateDep, CratePath};
+use ::models::crates::{CrateName, CrateRelease, CrateDeps, CrateDep, CrateFeatures, CratePath, DepFeatures};
+
+use super::status_error;
 
 const CRATES_INDEX_BASE_URI: &str = "https://raw.githubusercontent.com/rust-lang/crates.io-index";
 const CRATES_API_BASE_URI: &str = "https://crates.io/api/v1";
+/// Identifies deps.rs to the crates.io API, as required by its crawler policy
+const CRATES_API_USER_AGENT: &str = "deps.rs (https://github.com/srijs/deps.rs)";
 
 #[derive(Deserialize, Debug)]
 struct RegistryPackageDep {
     name: String,
     req: VersionReq,
     #[serde(default)]
-    kind: Option<String>
+    kind: Option<String>,
+    #[serde(default)]
+    package: Option<String>,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default = "default_true")]
+    default_features: bool,
+    #[serde(default)]
+    features: Vec<String>
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Deserialize, Debug)]
@@ -26,18 +48,47 @@ struct RegistryPackage {
     #[serde(default)]
     deps: Vec<RegistryPackageDep>,
     #[serde(default)]
-    yanked: bool
+    yanked: bool,
+    #[serde(default)]
+    features: IndexMap<String, Vec<String>>,
+    /// Features using the newer `dep:` and `?/` syntax, kept apart for older cargo versions
+    #[serde(default)]
+    features2: IndexMap<String, Vec<String>>
 }
 
 fn convert_pkgs(name: &CrateName, packages: Vec<RegistryPackage>) -> Result<QueryCrateResponse, Error> {
     let releases = packages.into_iter().map(|package| {
         let mut deps = CrateDeps::default();
+        let mut features = CrateFeatures::default();
+        features.declared = package.features;
+        features.declared.extend(package.features2);
         for dep in package.deps {
+            let dep_name = dep.name.parse::<CrateName>()?;
+            if dep.optional {
+                features.optional.push(dep_name.clone());
+            }
+            if !dep.default_features || !dep.features.is_empty() {
+                features.requested.insert(dep_name.clone(), DepFeatures {
+                    default_features: dep.default_features,
+                    features: dep.features
+                });
+            }
+
+            let crate_dep = match dep.package {
+                Some(ref package) => CrateDep::Renamed(package.parse()?, dep.req),
+                None => CrateDep::External(dep.req)
+            };
+            let target_deps = match dep.target {
+                Some(target) => deps.targets.entry(target).or_insert_with(CrateDeps::default),
+                None => &mut deps
+            };
             match dep.kind.map(|k| k.clone()).unwrap_or_else(|| "normal".into()).as_ref() {
                 "normal" =>
-                    deps.main.insert(dep.name.parse()?, CrateDep::External(dep.req)),
+                    target_deps.main.insert(dep_name, crate_dep),
                 "dev" =>
-                    deps.dev.insert(dep.name.parse()?, CrateDep::External(dep.req)),
+                    target_deps.dev.insert(dep_name, crate_dep),
+                "build" =>
+                    target_deps.build.insert(dep_name, crate_dep),
                 _ => None
             };
         }
@@ -45,6 +96,7 @@ fn convert_pkgs(name: &CrateName, packages: Vec<RegistryPackage>) -> Result<Quer
             name: name.clone(),
             version: package.vers,
             deps: deps,
+            features: features,
             yanked: package.yanked
         })
     }).collect::<Result<_, Error>>()?;
@@ -54,6 +106,7 @@ fn convert_pkgs(name: &CrateName, packages: Vec<RegistryPackage>) -> Result<Quer
     })
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct QueryCrateResponse {
     pub releases: Vec<CrateRelease>
 }
@@ -88,7 +141,7 @@ impl<S> Service for QueryCrate<S>
         Box::new(self.0.call(request).from_err().and_then(move |response| {
             let status = response.status();
             if !status.is_success() {
-                try_future!(Err(format_err!("Status code {} for URI {}", status, uri)));
+                try_future!(Err(status_error(status, response.headers(), &uri)));
             }
 
             let body_future = response.body().concat2().from_err();
@@ -109,6 +162,66 @@ impl<S> Service for QueryCrate<S>
     }
 }
 
+#[derive(Deserialize, Debug)]
+struct VersionsResponseDetail {
+    num: Version,
+    created_at: DateTime<Utc>
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionsResponse {
+    versions: Vec<VersionsResponseDetail>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QueryCrateVersionsResponse {
+    /// Publication date of every version, including yanked ones
+    pub published: HashMap<Version, DateTime<Utc>>
+}
+
+/// Fetches the publication dates of a crate's versions, which the index doesn't have.
+/// Requests name the newest version known from the index, so that the dates
+/// can be cached until a new version is published.
+#[derive(Debug, Clone)]
+pub struct QueryCrateVersions<S>(pub S);
+
+impl<S> Service for QueryCrateVersions<S>
+    where S: Service<Request=Request, Response=Response, Error=HyperError> + Clone + 'static,
+          S::Future: 'static
+{
+    type Request = CratePath;
+    type Response = QueryCrateVersionsResponse;
+    type Error = Error;
+    type Future = Box<Future<Item=Self::Response, Error=Self::Error>>;
+
+    fn call(&self, crate_path: CratePath) -> Self::Future {
+        let uri = try_future_box!(format!("{}/crates/{}/versions", CRATES_API_BASE_URI, crate_path.name.as_ref())
+            .parse::<Uri>());
+
+        let mut request = Request::new(Method::Get, uri.clone());
+        // crates.io rejects API requests without a user agent, and asks for a way to get in touch
+        request.headers_mut().set(UserAgent::new(CRATES_API_USER_AGENT));
+
+        Box::new(self.0.call(request).from_err().and_then(move |response| {
+            let status = response.status();
+            if !status.is_success() {
+                try_future!(Err(status_error(status, response.headers(), &uri)));
+            }
+
+            let body_future = response.body().concat2().from_err();
+            let decode_future = body_future.and_then(|body| {
+                let versions = serde_json::from_slice::<VersionsResponse>(&body)?;
+                let published = versions.versions.into_iter()
+                    .map(|version| (version.num, version.created_at))
+                    .collect();
+                Ok(QueryCrateVersionsResponse { published })
+            });
+
+            decode_future.into()
+        }))
+    }
+}
+
 #[derive(Deserialize)]
 struct SummaryResponseDetail {
     name: String,
@@ -146,12 +259,13 @@ impl<S> Service for GetPopularCrates<S>
             .parse::<Uri>().into_future().from_err();
 
         Box::new(uri_future.and_then(move |uri| {
-            let request = Request::new(Method::Get, uri.clone());
+            let mut request = Request::new(Method::Get, uri.clone());
+            request.headers_mut().set(UserAgent::new(CRATES_API_USER_AGENT));
 
             service.call(request).from_err().and_then(move |response| {
                 let status = response.status();
                 if !status.is_success() {
-                    future::Either::A(future::err(format_err!("Status code {} for URI {}", status, uri)))
+                    future::Either::A(future::err(status_error(status, response.headers(), &uri)))
                 } else {
                     let body_future = response.body().concat2().from_err();
                     let decode_future = body_future.and_then(|body| {
@@ -1,26 +1,69 @@
+use std::env;
+
 use failure::Error;
 use futures::{Future, Stream};
 use hyper::{Error as HyperError, Method, Request, Response, Uri};
-use hyper::header::UserAgent;
+use hyper::header::{Authorization, UserAgent};
 use relative_path::RelativePathBuf;
 use tokio_service::Service;
 use serde_json;
+use url::form_urlencoded;
 
 use ::models::repo::{Repository, RepoPath};
 
 const GITHUB_API_BASE_URI: &'static str = "https://api.github.com";
 const GITHUB_USER_CONTENT_BASE_URI: &'static str = "https://raw.githubusercontent.com";
 
+lazy_static! {
+    /// Optional API token, unauthenticated requests are limited to 60 per hour
+    static ref GITHUB_TOKEN: Option<String> = env::var("GITHUB_TOKEN").ok()
+        .and_then(|token| if token.is_empty() { None } else { Some(token) });
+}
+
+/// Authenticates a request to the GitHub API, if a token is configured
+pub fn authorize_api_request(request: &mut Request) {
+    if let Some(ref token) = *GITHUB_TOKEN {
+        request.headers_mut().set(Authorization(format!("token {}", token)));
+    }
+}
+
 pub fn get_manifest_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
     let path_str: &str = path.as_ref();
-    Ok(format!("{}/{}/{}/HEAD/{}",
+    Ok(format!("{}/{}/{}/{}/{}",
         GITHUB_USER_CONTENT_BASE_URI,
         repo_path.qual.as_ref(),
         repo_path.name.as_ref(),
+        repo_path.revision(),
         path_str
     ).parse::<Uri>()?)
 }
 
+pub fn get_tree_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
+    let path_str: &str = path.as_ref();
+    Ok(format!("{}/repos/{}/{}/contents/{}?ref={}",
+        GITHUB_API_BASE_URI,
+        repo_path.qual.as_ref(),
+        repo_path.name.as_ref(),
+        path_str.trim_matches('/'),
+        form_urlencoded::byte_serialize(repo_path.revision().as_bytes()).collect::<String>()
+    ).parse::<Uri>()?)
+}
+
+#[derive(Deserialize)]
+struct GithubContentsEntry {
+    name: String,
+    #[serde(rename = "type")]
+    entry_type: String
+}
+
+pub fn parse_tree_response(body: &[u8]) -> Result<Vec<String>, Error> {
+    let entries = serde_json::from_slice::<Vec<GithubContentsEntry>>(body)?;
+    Ok(entries.into_iter()
+        .filter(|entry| entry.entry_type == "dir")
+        .map(|entry| entry.name)
+        .collect())
+}
+
 #[derive(Deserialize)]
 struct GithubSearchResponse {
     items: Vec<GithubRepo>
@@ -56,6 +99,7 @@ impl<S> Service for GetPopularRepos<S>
 
         let mut request = Request::new(Method::Get, uri);
         request.headers_mut().set(UserAgent::new("deps.rs"));
+        authorize_api_request(&mut request);
 
         Box::new(self.0.call(request).from_err().and_then(|response| {
             let status = response.status();
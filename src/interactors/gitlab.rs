@@ -1,10 +1,14 @@
-use hyper::Uri;
+use hyper::{Headers, Uri};
+use hyper::header::{Link, RelationType};
 use relative_path::RelativePathBuf;
 use failure::Error;
+use serde_json;
+use url::form_urlencoded;
 
 use ::models::repo::RepoPath;
 
 const GITLAB_USER_CONTENT_BASE_URI: &'static str = "https://gitlab.com";
+const GITLAB_API_BASE_URI: &'static str = "https://gitlab.com/api/v4";
 
 pub fn get_manifest_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
     let path_str: &str = path.as_ref();
@@ -15,10 +19,50 @@ pub fn get_manifest_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<
     } else {
         path_str
     };
-    Ok(format!("{}/{}/{}/raw/HEAD/{}",
+    Ok(format!("{}/{}/{}/raw/{}/{}",
         GITLAB_USER_CONTENT_BASE_URI,
         repo_path.qual.as_ref(),
         repo_path.name.as_ref(),
+        repo_path.revision(),
         slash_path
     ).parse::<Uri>()?)
 }
+
+pub fn get_tree_uri(repo_path: &RepoPath, path: &RelativePathBuf) -> Result<Uri, Error> {
+    let path_str: &str = path.as_ref();
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("path", path_str.trim_matches('/'))
+        .append_pair("ref", repo_path.revision())
+        .append_pair("per_page", "100")
+        .finish();
+    Ok(format!("{}/projects/{}%2F{}/repository/tree?{}",
+        GITLAB_API_BASE_URI,
+        repo_path.qual.as_ref(),
+        repo_path.name.as_ref(),
+        query
+    ).parse::<Uri>()?)
+}
+
+#[derive(Deserialize)]
+struct GitlabTreeEntry {
+    name: String,
+    #[serde(rename = "type")]
+    entry_type: String
+}
+
+/// Returns the next page of a listing, from the `Link` header gitlab paginates with
+pub fn parse_next_page_uri(headers: &Headers) -> Option<Uri> {
+    headers.get::<Link>().and_then(|link| {
+        link.values().iter()
+            .find(|value| value.rel().map(|rels| rels.contains(&RelationType::Next)).unwrap_or(false))
+            .and_then(|value| value.link().parse::<Uri>().ok())
+    })
+}
+
+pub fn parse_tree_response(body: &[u8]) -> Result<Vec<String>, Error> {
+    let entries = serde_json::from_slice::<Vec<GitlabTreeEntry>>(body)?;
+    Ok(entries.into_iter()
+        .filter(|entry| entry.entry_type == "tree")
+        .map(|entry| entry.name)
+        .collect())
+}
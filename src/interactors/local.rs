@@ -0,0 +1,139 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use futures::future::{self, FutureResult};
+use relative_path::RelativePathBuf;
+use tokio_service::Service;
+
+use super::{UpstreamError, UpstreamErrorKind};
+
+#[derive(Debug, Clone)]
+pub struct RetrieveLocalFile;
+
+impl Service for RetrieveLocalFile {
+    type Request = (PathBuf, RelativePathBuf);
+    type Response = String;
+    type Error = Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let (root, path) = req;
+        let file_path = path.to_path(&root);
+
+        future::result(fs::read_to_string(&file_path)
+            .map_err(|err| io_error(err, "read", &file_path)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ListLocalDirectory;
+
+impl Service for ListLocalDirectory {
+    type Request = (PathBuf, RelativePathBuf);
+    type Response = Vec<String>;
+    type Error = Error;
+    type Future = FutureResult<Self::Response, Self::Error>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let (root, path) = req;
+        let dir_path = path.to_path(&root);
+
+        future::result(list_subdirectories(&dir_path)
+            .map_err(|err| io_error(err, "list", &dir_path)))
+    }
+}
+
+/// Classifies missing local files like missing files upstream
+fn io_error(err: io::Error, action: &str, path: &Path) -> Error {
+    let message = format!("failed to {} {}: {}", action, path.display(), err);
+    if err.kind() == io::ErrorKind::NotFound {
+        UpstreamError::new(UpstreamErrorKind::NotFound, message).into()
+    } else {
+        format_err!("{}", message)
+    }
+}
+
+fn list_subdirectories(dir_path: &Path) -> Result<Vec<String>, io::Error> {
+    let mut names = vec![];
+    for entry in fs::read_dir(dir_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use futures::Future;
+    use relative_path::RelativePathBuf;
+    use tokio_service::Service;
+
+    use interactors::{UpstreamError, UpstreamErrorKind};
+    use super::{ListLocalDirectory, RetrieveLocalFile};
+
+    /// A project directory that is removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = env::temp_dir().join(format!("deps-rs-local-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn retrieves_files_relative_to_root() {
+        let dir = TempDir::new("retrieve");
+        fs::create_dir_all(dir.0.join("crates/foo")).unwrap();
+        fs::write(dir.0.join("crates/foo/Cargo.toml"), "[package]").unwrap();
+
+        let contents = RetrieveLocalFile.call((dir.0.clone(), RelativePathBuf::from("crates/foo/Cargo.toml")))
+            .wait().unwrap();
+        assert_eq!(contents, "[package]");
+    }
+
+    #[test]
+    fn reports_missing_files_as_not_found() {
+        let dir = TempDir::new("missing");
+
+        let err = RetrieveLocalFile.call((dir.0.clone(), RelativePathBuf::from("Cargo.lock")))
+            .wait().unwrap_err();
+        assert_eq!(UpstreamError::classify(&err), UpstreamErrorKind::NotFound);
+
+        let err = ListLocalDirectory.call((dir.0.clone(), RelativePathBuf::from("crates")))
+            .wait().unwrap_err();
+        assert_eq!(UpstreamError::classify(&err), UpstreamErrorKind::NotFound);
+    }
+
+    #[test]
+    fn lists_only_subdirectories() {
+        let dir = TempDir::new("list");
+        fs::create_dir_all(dir.0.join("crates/foo")).unwrap();
+        fs::create_dir_all(dir.0.join("crates/bar")).unwrap();
+        fs::write(dir.0.join("crates/README.md"), "").unwrap();
+
+        let mut names = ListLocalDirectory.call((dir.0.clone(), RelativePathBuf::from("crates")))
+            .wait().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["bar".to_string(), "foo".to_string()]);
+    }
+}
@@ -1,7 +1,12 @@
-use failure::Error;
-use futures::{Future, Stream};
-use hyper::{Error as HyperError, Method, Request, Response};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use failure::{Error, Fail};
+use futures::{Future, Stream, future};
+use futures::future::Loop;
+use hyper::{Error as HyperError, Headers, Method, Request, Response, StatusCode, Uri};
+use hyper::header::UserAgent;
 use relative_path::RelativePathBuf;
+use shared_failure::SharedFailure;
 use tokio_service::Service;
 
 use ::models::repo::{RepoSite, RepoPath};
@@ -10,8 +15,80 @@ pub mod bitbucket;
 pub mod crates;
 pub mod github;
 pub mod gitlab;
+pub mod local;
 pub mod rustsec;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpstreamErrorKind {
+    NotFound,
+    RateLimited,
+    Transient
+}
+
+/// A failed request to one of the upstream services
+#[derive(Debug)]
+pub struct UpstreamError {
+    kind: UpstreamErrorKind,
+    message: String
+}
+
+impl UpstreamError {
+    pub fn new(kind: UpstreamErrorKind, message: String) -> UpstreamError {
+        UpstreamError { kind, message }
+    }
+
+    pub fn kind(&self) -> UpstreamErrorKind {
+        self.kind
+    }
+
+    /// Classifies an error, looking through errors shared by a cache.
+    /// Anything that isn't known to be permanent is considered transient.
+    pub fn classify(err: &Error) -> UpstreamErrorKind {
+        err.downcast_ref::<SharedFailure>()
+            .map(UpstreamError::classify_shared)
+            .or_else(|| err.downcast_ref::<UpstreamError>().map(UpstreamError::kind))
+            .unwrap_or(UpstreamErrorKind::Transient)
+    }
+
+    pub fn classify_shared(err: &SharedFailure) -> UpstreamErrorKind {
+        err.downcast_ref::<UpstreamError>()
+            .map(UpstreamError::kind)
+            .unwrap_or(UpstreamErrorKind::Transient)
+    }
+}
+
+impl Display for UpstreamError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.write_str(&self.message)
+    }
+}
+
+impl Fail for UpstreamError {}
+
+/// Whether a response carries the headers that APIs send along with exhausted rate limits
+fn is_rate_limited(headers: &Headers) -> bool {
+    let remaining = headers.get_raw("X-RateLimit-Remaining").and_then(|raw| raw.one());
+    remaining == Some(b"0") || headers.get_raw("Retry-After").is_some()
+}
+
+pub fn status_error(status: StatusCode, headers: &Headers, uri: &Uri) -> Error {
+    let kind = match status {
+        StatusCode::NotFound | StatusCode::Gone =>
+            UpstreamErrorKind::NotFound,
+        StatusCode::TooManyRequests =>
+            UpstreamErrorKind::RateLimited,
+        // GitHub signals exhausted API rate limits with a 403, while otherwise
+        // a 403 means that the resource is private
+        StatusCode::Forbidden if is_rate_limited(headers) =>
+            UpstreamErrorKind::RateLimited,
+        StatusCode::Forbidden =>
+            UpstreamErrorKind::NotFound,
+        _ =>
+            UpstreamErrorKind::Transient
+    };
+    UpstreamError::new(kind, format!("Status code {} for URI {}", status, uri)).into()
+}
+
 #[derive(Debug, Clone)]
 pub struct RetrieveFileAtPath<S>(pub S);
 
@@ -43,7 +120,7 @@ impl<S> Service for RetrieveFileAtPath<S>
         Box::new(self.0.call(request).from_err().and_then(move |response| {
             let status = response.status();
             if !status.is_success() {
-                try_future!(Err(format_err!("Status code {} for URI {}", status, uri)));
+                try_future!(Err(status_error(status, response.headers(), &uri)));
             }
 
             let body_future = response.body().concat2().from_err();
@@ -55,4 +132,113 @@ impl<S> Service for RetrieveFileAtPath<S>
     }
 }
 
+/// Upper bound on the pages of a directory listing that are followed
+const MAX_LISTING_PAGES: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct ListDirectoryAtPath<S>(pub S);
+
+impl<S> Service for ListDirectoryAtPath<S>
+    where S: Service<Request=Request, Response=Response, Error=HyperError> + Clone + 'static,
+          S::Future: 'static
+{
+    type Request = (RepoPath, RelativePathBuf);
+    type Response = Vec<String>;
+    type Error = Error;
+    type Future = Box<Future<Item=Self::Response, Error=Self::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let (repo_path, path) = req;
+        let site = repo_path.site;
+        let uri = match &site {
+            &RepoSite::Github => {
+                try_future_box!(github::get_tree_uri(&repo_path, &path))
+            },
+            &RepoSite::Gitlab => {
+                try_future_box!(gitlab::get_tree_uri(&repo_path, &path))
+            },
+            &RepoSite::Bitbucket => {
+                try_future_box!(bitbucket::get_tree_uri(&repo_path, &path))
+            }
+        };
+
+        let service = self.0.clone();
+
+        Box::new(future::loop_fn((uri, vec![], 1), move |(uri, mut names, page): (Uri, Vec<String>, usize)| {
+            let mut request = Request::new(Method::Get, uri.clone());
+            request.headers_mut().set(UserAgent::new("deps.rs"));
+            if site == RepoSite::Github {
+                github::authorize_api_request(&mut request);
+            }
+
+            service.call(request).from_err().and_then(move |response| {
+                let status = response.status();
+                if !status.is_success() {
+                    try_future!(Err(status_error(status, response.headers(), &uri)));
+                }
+
+                let next_uri = match site {
+                    RepoSite::Gitlab => gitlab::parse_next_page_uri(response.headers()),
+                    _ => None
+                };
+                let body_future = response.body().concat2().from_err();
+
+                body_future
+                    .and_then(move |body| {
+                        let (page_names, next_uri) = match site {
+                            RepoSite::Github => (github::parse_tree_response(body.as_ref())?, None),
+                            RepoSite::Gitlab => (gitlab::parse_tree_response(body.as_ref())?, next_uri),
+                            RepoSite::Bitbucket => bitbucket::parse_tree_response(body.as_ref())?
+                        };
+                        names.extend(page_names);
+
+                        match next_uri {
+                            Some(next_uri) if page < MAX_LISTING_PAGES =>
+                                Ok(Loop::Continue((next_uri, names, page + 1))),
+                            _ => Ok(Loop::Break(names))
+                        }
+                    })
+                    .into()
+            })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Headers, StatusCode, Uri};
+
+    use super::{UpstreamError, UpstreamErrorKind, status_error};
+
+    fn classify(status: StatusCode, headers: &Headers) -> UpstreamErrorKind {
+        let uri = "https://api.github.com/repos/foo/bar".parse::<Uri>().unwrap();
+        UpstreamError::classify(&status_error(status, headers, &uri))
+    }
 
+    #[test]
+    fn classifies_status_codes() {
+        let headers = Headers::new();
+        assert_eq!(classify(StatusCode::NotFound, &headers), UpstreamErrorKind::NotFound);
+        assert_eq!(classify(StatusCode::Gone, &headers), UpstreamErrorKind::NotFound);
+        assert_eq!(classify(StatusCode::TooManyRequests, &headers), UpstreamErrorKind::RateLimited);
+        assert_eq!(classify(StatusCode::InternalServerError, &headers), UpstreamErrorKind::Transient);
+        assert_eq!(classify(StatusCode::BadGateway, &headers), UpstreamErrorKind::Transient);
+    }
+
+    #[test]
+    fn classifies_forbidden_by_rate_limit_headers() {
+        assert_eq!(classify(StatusCode::Forbidden, &Headers::new()), UpstreamErrorKind::NotFound);
+
+        let mut exhausted = Headers::new();
+        exhausted.set_raw("X-RateLimit-Remaining", "0");
+        assert_eq!(classify(StatusCode::Forbidden, &exhausted), UpstreamErrorKind::RateLimited);
+
+        let mut remaining = Headers::new();
+        remaining.set_raw("X-RateLimit-Remaining", "42");
+        assert_eq!(classify(StatusCode::Forbidden, &remaining), UpstreamErrorKind::NotFound);
+
+        let mut retry_after = Headers::new();
+        retry_after.set_raw("Retry-After", "60");
+        assert_eq!(classify(StatusCode::Forbidden, &retry_after), UpstreamErrorKind::RateLimited);
+    }
+}
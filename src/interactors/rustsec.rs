@@ -1,4 +1,4 @@
-use std::str;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use failure::Error;
@@ -6,8 +6,49 @@ use futures::{Future, IntoFuture, Stream, future};
 use hyper::{Error as HyperError, Method, Request, Response};
 use rustsec::ADVISORY_DB_URL;
 use rustsec::db::AdvisoryDatabase;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeserializeError;
 use tokio_service::Service;
 
+use ::models::crates::AdvisoryMetadata;
+use ::parsers::advisories::parse_advisory_metadata;
+
+/// The parsed advisory database, along with the TOML it was parsed from
+/// so that it can be persisted
+#[derive(Clone)]
+pub struct AdvisoryDatabaseResponse {
+    pub db: Arc<AdvisoryDatabase>,
+    /// Kinds and CVSS scores of the advisories, keyed by ID
+    pub metadata: Arc<HashMap<String, AdvisoryMetadata>>,
+    source: Arc<String>
+}
+
+impl AdvisoryDatabaseResponse {
+    fn from_toml(source: String) -> Result<AdvisoryDatabaseResponse, Error> {
+        let db = AdvisoryDatabase::from_toml(&source)?;
+        // advisories without metadata are treated as plain vulnerabilities
+        let metadata = parse_advisory_metadata(&source).unwrap_or_default();
+        Ok(AdvisoryDatabaseResponse {
+            db: Arc::new(db),
+            metadata: Arc::new(metadata),
+            source: Arc::new(source)
+        })
+    }
+}
+
+impl Serialize for AdvisoryDatabaseResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for AdvisoryDatabaseResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<AdvisoryDatabaseResponse, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        AdvisoryDatabaseResponse::from_toml(source).map_err(DeserializeError::custom)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FetchAdvisoryDatabase<S>(pub S);
 
@@ -16,7 +57,7 @@ impl<S> Service for FetchAdvisoryDatabase<S>
           S::Future: 'static
 {
     type Request = ();
-    type Response = Arc<AdvisoryDatabase>;
+    type Response = AdvisoryDatabaseResponse;
     type Error = Error;
     type Future = Box<Future<Item=Self::Response, Error=Self::Error>>;
 
@@ -35,7 +76,7 @@ impl<S> Service for FetchAdvisoryDatabase<S>
                 } else {
                     let body_future = response.body().concat2().from_err();
                     let decode_future = body_future
-                        .and_then(|body| Ok(Arc::new(AdvisoryDatabase::from_toml(str::from_utf8(&body)?)?)));
+                        .and_then(|body| AdvisoryDatabaseResponse::from_toml(String::from_utf8(body.to_vec())?));
                     future::Either::B(decode_future)
                 }
             })
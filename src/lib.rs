@@ -0,0 +1,35 @@
+#![feature(proc_macro_non_items)]
+
+extern crate badge;
+extern crate cadence;
+extern crate chrono;
+#[macro_use] extern crate failure;
+#[macro_use] extern crate futures;
+extern crate futures_cpupool;
+extern crate hyper;
+extern crate hyper_tls;
+extern crate indexmap;
+#[macro_use] extern crate lazy_static;
+extern crate lru_cache;
+extern crate maud;
+extern crate relative_path;
+extern crate route_recognizer;
+extern crate rustsec;
+extern crate semver;
+#[macro_use] extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+extern crate shared_failure;
+#[macro_use] extern crate slog;
+extern crate tokio_core;
+extern crate tokio_service;
+extern crate toml;
+#[macro_use] extern crate try_future;
+extern crate url;
+
+mod parsers;
+mod interactors;
+pub mod utils;
+pub mod models;
+pub mod engine;
+pub mod server;
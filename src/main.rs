@@ -1,40 +1,15 @@
-#![feature(proc_macro_non_items)]
-
-extern crate badge;
 extern crate cadence;
-#[macro_use] extern crate failure;
-#[macro_use] extern crate futures;
+extern crate futures;
 extern crate hyper;
 extern crate hyper_tls;
-extern crate indexmap;
-#[macro_use] extern crate lazy_static;
-extern crate lru_cache;
-extern crate maud;
-extern crate relative_path;
-extern crate route_recognizer;
-extern crate rustsec;
-extern crate semver;
-#[macro_use] extern crate serde_derive;
-extern crate serde;
-extern crate serde_json;
-extern crate shared_failure;
+extern crate shiny_robots;
 #[macro_use] extern crate slog;
 extern crate slog_json;
 extern crate tokio_core;
-extern crate tokio_service;
-extern crate toml;
-#[macro_use] extern crate try_future;
-
-mod utils;
-mod models;
-mod parsers;
-mod interactors;
-mod engine;
-mod server;
 
 use std::env;
 use std::net::{IpAddr, Ipv4Addr, UdpSocket, SocketAddr};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use cadence::{QueuingMetricSink, UdpMetricSink};
 use futures::{Future, Stream};
@@ -44,8 +19,9 @@ use hyper_tls::HttpsConnector;
 use slog::Drain;
 use tokio_core::reactor::Core;
 
-use self::server::Server;
-use self::engine::Engine;
+use shiny_robots::server::Server;
+use shiny_robots::engine::Engine;
+use shiny_robots::utils::store::{FileStore, Store};
 
 fn init_metrics() -> QueuingMetricSink {
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -82,7 +58,10 @@ fn main() {
 
     let http = Http::new();
 
-    let mut engine = Engine::new(client.clone(), logger.clone());
+    let store = env::var("CACHE_DIR").ok()
+        .map(|dir| Arc::new(FileStore::new(dir)) as Arc<Store>);
+
+    let mut engine = Engine::new(client.clone(), logger.clone(), store);
     engine.set_metrics(metrics);
 
     let server = Server::new(logger.clone(), engine);
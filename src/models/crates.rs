@@ -21,7 +21,7 @@ impl CratePath {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct CrateName(String);
 
 impl Into<String> for CrateName {
@@ -58,95 +58,373 @@ impl FromStr for CrateName {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CrateRelease {
     pub name: CrateName,
     pub version: Version,
     pub deps: CrateDeps,
+    #[serde(default)]
+    pub features: CrateFeatures,
     pub yanked: bool
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Features a crate enables on one of its dependencies
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepFeatures {
+    pub default_features: bool,
+    pub features: Vec<String>
+}
+
+impl Default for DepFeatures {
+    fn default() -> DepFeatures {
+        DepFeatures { default_features: true, features: vec![] }
+    }
+}
+
+/// Feature declarations of a release, to tell which of its optional dependencies are built
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CrateFeatures {
+    /// Features and what they enable, e.g. `"tls" => ["native-tls", "hyper/tls"]`
+    pub declared: IndexMap<String, Vec<String>>,
+    /// Declared names of the optional dependencies
+    pub optional: Vec<CrateName>,
+    /// Features enabled on dependencies, by declared name
+    pub requested: IndexMap<CrateName, DepFeatures>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CrateDep {
     External(VersionReq),
-    Internal(RelativePathBuf)
+    /// An external dependency that is renamed locally using the `package` key
+    Renamed(CrateName, VersionReq),
+    Internal(RelativePathBuf),
+    /// Declared with `workspace = true`, to be resolved against
+    /// the `[workspace.dependencies]` of the workspace root
+    Inherited
 }
 
 impl CrateDep {
     pub fn is_external(&self) -> bool {
-        if let &CrateDep::External(_) = self {
-            true
-        } else {
-            false
+        match self {
+            &CrateDep::External(_) | &CrateDep::Renamed(_, _) => true,
+            _ => false
+        }
+    }
+
+    /// Returns the name of the registry package along with the requirement,
+    /// given the (possibly aliased) name this dependency is declared under
+    pub fn external_package<'a>(&'a self, name: &'a CrateName) -> Option<(&'a CrateName, &'a VersionReq)> {
+        match self {
+            &CrateDep::External(ref req) => Some((name, req)),
+            &CrateDep::Renamed(ref package, ref req) => Some((package, req)),
+            _ => None
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CrateDeps {
     pub main: IndexMap<CrateName, CrateDep>,
     pub dev: IndexMap<CrateName, CrateDep>,
-    pub build: IndexMap<CrateName, CrateDep>
+    pub build: IndexMap<CrateName, CrateDep>,
+    /// Platform-specific dependencies, keyed by target expression
+    pub targets: IndexMap<String, CrateDeps>
+}
+
+impl CrateDeps {
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item=(&'a CrateName, &'a CrateDep)> + 'a> {
+        Box::new(self.main.iter().chain(self.dev.iter()).chain(self.build.iter())
+            .chain(self.targets.values().flat_map(|deps| deps.iter())))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CrateLockfile {
+    pub packages: IndexMap<CrateName, Vec<Version>>
+}
+
+impl CrateLockfile {
+    pub fn find_locked(&self, name: &CrateName, req: &VersionReq) -> Option<&Version> {
+        self.packages.get(name).and_then(|versions| {
+            versions.iter()
+                .filter(|version| req.matches(version))
+                .max()
+        })
+    }
+}
+
+/// Kind of a RustSec advisory, ordered by severity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AdvisoryKind {
+    Notice,
+    Unmaintained,
+    Unsound,
+    Vulnerability
+}
+
+impl Default for AdvisoryKind {
+    fn default() -> AdvisoryKind {
+        AdvisoryKind::Vulnerability
+    }
+}
+
+impl AdvisoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            AdvisoryKind::Notice => "notice",
+            AdvisoryKind::Unmaintained => "unmaintained",
+            AdvisoryKind::Unsound => "unsound",
+            AdvisoryKind::Vulnerability => "vulnerability"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cvss {
+    pub vector: String,
+    pub base_score: f64
+}
+
+/// Advisory fields that the advisory database parser doesn't expose
+#[derive(Clone, Debug, Default)]
+pub struct AdvisoryMetadata {
+    pub kind: AdvisoryKind,
+    pub cvss: Option<Cvss>
+}
+
+/// RustSec advisory affecting an analyzed dependency
+#[derive(Clone, Debug)]
+pub struct Advisory {
+    pub id: String,
+    pub kind: AdvisoryKind,
+    pub cvss: Option<Cvss>,
+    pub title: String,
+    pub url: Option<String>,
+    pub patched_versions: Vec<VersionReq>,
+    /// Reason for ignoring the advisory, as stated in the policy
+    pub ignored: Option<String>
+}
+
+impl Advisory {
+    pub fn is_patched(&self, version: &Version) -> bool {
+        self.patched_versions.iter().any(|req| req.matches(version))
+    }
+}
+
+/// Most significant semver component that differs between a release and a later one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LagKind {
+    Patch,
+    Minor,
+    Major
+}
+
+impl LagKind {
+    /// Classifies the upgrade to a later version. As in cargo, a change of the
+    /// leftmost non-zero component is breaking and counts as major.
+    pub fn between(version: &Version, later: &Version) -> Option<LagKind> {
+        if later <= version {
+            return None;
+        }
+
+        let is_compatible = if version.major > 0 {
+            version.major == later.major
+        } else if version.minor > 0 {
+            later.major == 0 && version.minor == later.minor
+        } else {
+            later.major == 0 && later.minor == 0 && version.patch == later.patch
+        };
+
+        if !is_compatible {
+            Some(LagKind::Major)
+        } else if version.major != later.major || version.minor != later.minor {
+            Some(LagKind::Minor)
+        } else {
+            Some(LagKind::Patch)
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LagKind::Patch => "patch",
+            LagKind::Minor => "minor",
+            LagKind::Major => "major"
+        }
+    }
+}
+
+/// How far the requirement of a dependency trails its latest release
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lag {
+    pub kind: LagKind,
+    /// Number of stable releases newer than the requirement allows
+    pub releases: usize
 }
 
 #[derive(Debug)]
 pub struct AnalyzedDependency {
+    /// Name of the registry package, if it differs from the declared name
+    pub package: Option<CrateName>,
     pub required: VersionReq,
+    pub locked: Option<Version>,
     pub latest_that_matches: Option<Version>,
     pub latest: Option<Version>,
-    pub insecure: bool
+    /// Whether any yanked release matches the requirement
+    pub matches_yanked: bool,
+    pub advisories: Vec<Advisory>,
+    /// Lowest release that is patched against all of the advisories
+    pub safe_version: Option<Version>,
+    pub lag: Option<Lag>,
+    /// Time between the publication of the version in use and of the latest release,
+    /// in years (see https://libyear.com)
+    pub libyears: Option<f64>,
+    /// Reason for ignoring the dependency, as stated in the policy
+    pub ignored: Option<String>,
+    /// Whether the policy accepts the dependency trailing its latest release
+    pub outdated_allowed: bool
 }
 
 impl AnalyzedDependency {
     pub fn new(required: VersionReq) -> AnalyzedDependency {
         AnalyzedDependency {
+            package: None,
             required,
+            locked: None,
             latest_that_matches: None,
             latest: None,
-            insecure: false
+            matches_yanked: false,
+            advisories: vec![],
+            safe_version: None,
+            lag: None,
+            libyears: None,
+            ignored: None,
+            outdated_allowed: false
         }
     }
 
-    pub fn is_outdated(&self) -> bool {
+    fn from_dep(name: &CrateName, dep: &CrateDep) -> Option<AnalyzedDependency> {
+        dep.external_package(name).map(|(package, req)| {
+            let mut analyzed = AnalyzedDependency::new(req.clone());
+            if package != name {
+                analyzed.package = Some(package.clone());
+            }
+            analyzed
+        })
+    }
+
+    pub fn package_name<'a>(&'a self, name: &'a CrateName) -> &'a CrateName {
+        self.package.as_ref().unwrap_or(name)
+    }
+
+    /// Whether any of the advisories is an actual vulnerability
+    pub fn is_insecure(&self) -> bool {
+        self.advisory_kind() == Some(AdvisoryKind::Vulnerability)
+    }
+
+    /// Most severe kind of the advisories that aren't ignored
+    pub fn advisory_kind(&self) -> Option<AdvisoryKind> {
+        if self.ignored.is_some() {
+            return None;
+        }
+        self.advisories.iter()
+            .filter(|advisory| advisory.ignored.is_none())
+            .map(|advisory| advisory.kind)
+            .max()
+    }
+
+    /// Whether there is a newer release than the requirement allows, regardless of the policy
+    pub fn is_behind_latest(&self) -> bool {
         self.latest > self.latest_that_matches
     }
+
+    pub fn is_outdated(&self) -> bool {
+        self.ignored.is_none() && !self.outdated_allowed && self.latest_that_matches.is_some() && self.is_behind_latest()
+    }
+
+    /// Whether the requirement only matches releases that have been yanked
+    pub fn is_yanked_only(&self) -> bool {
+        self.ignored.is_none() && self.latest_that_matches.is_none() && self.matches_yanked
+    }
+
+    /// Whether no release at all matches the requirement
+    pub fn is_unsatisfiable(&self) -> bool {
+        self.ignored.is_none() && self.latest_that_matches.is_none() && !self.matches_yanked
+    }
+
+    /// Whether the dependency is outdated by a breaking release
+    pub fn is_major_outdated(&self) -> bool {
+        self.is_outdated() && self.lag.as_ref().map(|lag| lag.kind) == Some(LagKind::Major)
+    }
+
+    pub fn is_locked_outdated(&self) -> bool {
+        match (&self.locked, &self.latest_that_matches) {
+            (&Some(ref locked), &Some(ref latest_that_matches)) => locked < latest_that_matches,
+            _ => false
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TransitiveDependency {
+    pub name: CrateName,
+    pub version: Version,
+    /// Chain of crates through which this dependency is pulled in,
+    /// starting with a direct dependency
+    pub path: Vec<CrateName>,
+    /// Most severe kind of the advisories affecting this version
+    pub advisory_kind: Option<AdvisoryKind>,
+    pub yanked: bool
+}
+
+impl TransitiveDependency {
+    pub fn is_insecure(&self) -> bool {
+        self.advisory_kind == Some(AdvisoryKind::Vulnerability)
+    }
 }
 
 #[derive(Debug)]
 pub struct AnalyzedDependencies {
     pub main: IndexMap<CrateName, AnalyzedDependency>,
     pub dev: IndexMap<CrateName, AnalyzedDependency>,
-    pub build: IndexMap<CrateName, AnalyzedDependency>
+    pub build: IndexMap<CrateName, AnalyzedDependency>,
+    pub targets: IndexMap<String, AnalyzedDependencies>,
+    pub transitive: Vec<TransitiveDependency>,
+    /// Whether the dependency tree was too large to be resolved completely
+    pub transitive_truncated: bool
 }
 
 impl AnalyzedDependencies {
     pub fn new(deps: &CrateDeps) -> AnalyzedDependencies {
         let main = deps.main.iter().filter_map(|(name, dep)| {
-            if let &CrateDep::External(ref req) = dep {
-                Some((name.clone(), AnalyzedDependency::new(req.clone())))
-            } else {
-                None
-            }
+            AnalyzedDependency::from_dep(name, dep).map(|analyzed| (name.clone(), analyzed))
         }).collect();
         let dev = deps.dev.iter().filter_map(|(name, dep)| {
-            if let &CrateDep::External(ref req) = dep {
-                Some((name.clone(), AnalyzedDependency::new(req.clone())))
-            } else {
-                None
-            }
+            AnalyzedDependency::from_dep(name, dep).map(|analyzed| (name.clone(), analyzed))
         }).collect();
         let build = deps.build.iter().filter_map(|(name, dep)| {
-            if let &CrateDep::External(ref req) = dep {
-                Some((name.clone(), AnalyzedDependency::new(req.clone())))
-            } else {
-                None
-            }
+            AnalyzedDependency::from_dep(name, dep).map(|analyzed| (name.clone(), analyzed))
         }).collect();
-        AnalyzedDependencies { main, dev, build }
+        let targets = deps.targets.iter().map(|(target, deps)| {
+            (target.clone(), AnalyzedDependencies::new(deps))
+        }).collect();
+        AnalyzedDependencies { main, dev, build, targets, transitive: vec![], transitive_truncated: false }
+    }
+
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item=(&'a CrateName, &'a AnalyzedDependency)> + 'a> {
+        Box::new(self.main.iter().chain(self.dev.iter()).chain(self.build.iter())
+            .chain(self.targets.values().flat_map(|deps| deps.iter())))
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> Box<Iterator<Item=(&'a CrateName, &'a mut AnalyzedDependency)> + 'a> {
+        Box::new(self.main.iter_mut().chain(self.dev.iter_mut()).chain(self.build.iter_mut())
+            .chain(self.targets.values_mut().flat_map(|deps| deps.iter_mut())))
     }
 
     pub fn count_total(&self) -> usize {
-        self.main.len() + self.dev.len() + self.build.len()
+        let targets_total = self.targets.values()
+            .map(|deps| deps.count_total())
+            .sum::<usize>();
+        self.main.len() + self.dev.len() + self.build.len() + targets_total
     }
 
     pub fn count_outdated(&self) -> usize {
@@ -159,22 +437,58 @@ impl AnalyzedDependencies {
         let build_outdated = self.build.iter()
             .filter(|&(_, dep)| dep.is_outdated())
             .count();
-        main_outdated + dev_outdated + build_outdated
+        let targets_outdated = self.targets.values()
+            .map(|deps| deps.count_outdated())
+            .sum::<usize>();
+        main_outdated + dev_outdated + build_outdated + targets_outdated
+    }
+
+    /// Sum of the libyears of all dependencies with known publication dates
+    pub fn libyears(&self) -> f64 {
+        self.iter().filter_map(|(_, dep)| dep.libyears).sum()
+    }
+
+    pub fn count_major_outdated(&self) -> usize {
+        self.iter().filter(|&(_, dep)| dep.is_major_outdated()).count()
     }
 
      pub fn count_insecure(&self) -> usize {
         let main_insecure = self.main.iter()
-            .filter(|&(_, dep)| dep.insecure)
+            .filter(|&(_, dep)| dep.is_insecure())
             .count();
         let dev_insecure = self.dev.iter()
-            .filter(|&(_, dep)| dep.insecure)
+            .filter(|&(_, dep)| dep.is_insecure())
             .count();
         let build_insecure = self.build.iter()
-            .filter(|&(_, dep)| dep.insecure)
+            .filter(|&(_, dep)| dep.is_insecure())
             .count();
-        main_insecure + dev_insecure + build_insecure
+        let targets_insecure = self.targets.values()
+            .map(|deps| deps.count_insecure())
+            .sum::<usize>();
+        main_insecure + dev_insecure + build_insecure + targets_insecure
     } 
 
+    pub fn count_transitive_insecure(&self) -> usize {
+        self.transitive.iter()
+            .filter(|dep| dep.is_insecure())
+            .count()
+    }
+
+    /// Most severe kind of advisory affecting any direct or transitive dependency
+    pub fn advisory_kind(&self) -> Option<AdvisoryKind> {
+        let direct = self.iter().filter_map(|(_, dep)| dep.advisory_kind()).max();
+        let transitive = self.transitive.iter().filter_map(|dep| dep.advisory_kind).max();
+        direct.max(transitive)
+    }
+
+    pub fn any_yanked_only(&self) -> bool {
+        self.iter().any(|(_, dep)| dep.is_yanked_only())
+    }
+
+    pub fn any_unsatisfiable(&self) -> bool {
+        self.iter().any(|(_, dep)| dep.is_unsatisfiable())
+    }
+
     pub fn any_outdated(&self) -> bool {
         let main_any_outdated = self.main.iter()
             .any(|(_, dep)| dep.is_outdated());
@@ -182,13 +496,43 @@ impl AnalyzedDependencies {
             .any(|(_, dep)| dep.is_outdated());
         let build_any_outdated = self.build.iter()
             .any(|(_, dep)| dep.is_outdated());
-        main_any_outdated || dev_any_outdated || build_any_outdated
+        let targets_any_outdated = self.targets.values()
+            .any(|deps| deps.any_outdated());
+        main_any_outdated || dev_any_outdated || build_any_outdated || targets_any_outdated
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum CrateManifest {
     Package(CrateName, CrateDeps),
-    Workspace { members: Vec<RelativePathBuf> },
-    Mixed { name: CrateName, deps: CrateDeps, members: Vec<RelativePathBuf> }
+    Workspace {
+        members: Vec<RelativePathBuf>,
+        exclude: Vec<RelativePathBuf>,
+        dependencies: IndexMap<CrateName, CrateDep>
+    },
+    Mixed {
+        name: CrateName,
+        deps: CrateDeps,
+        members: Vec<RelativePathBuf>,
+        exclude: Vec<RelativePathBuf>,
+        dependencies: IndexMap<CrateName, CrateDep>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LagKind;
+
+    #[test]
+    fn classifies_lag() {
+        let between = |version: &str, later: &str| LagKind::between(&version.parse().unwrap(), &later.parse().unwrap());
+
+        assert_eq!(between("1.2.3", "1.2.3"), None);
+        assert_eq!(between("1.2.3", "1.2.4"), Some(LagKind::Patch));
+        assert_eq!(between("1.2.3", "1.4.0"), Some(LagKind::Minor));
+        assert_eq!(between("1.2.3", "2.0.0"), Some(LagKind::Major));
+        assert_eq!(between("0.3.1", "0.3.4"), Some(LagKind::Patch));
+        assert_eq!(between("0.3.1", "0.4.0"), Some(LagKind::Major));
+        assert_eq!(between("0.0.1", "0.0.2"), Some(LagKind::Major));
+    }
 }
@@ -1,5 +1,6 @@
 pub mod crates;
 pub mod repo;
+pub mod policy;
 
 pub enum SubjectPath {
     Repo(self::repo::RepoPath),
@@ -0,0 +1,47 @@
+use std::hash::{Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use super::crates::CrateName;
+
+/// Rules for acknowledging known issues, committed to the analyzed repository
+/// as `.deps.toml` or `[package.metadata.deps-rs]`
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// Don't count dependencies as outdated if they only trail by semver-compatible releases
+    pub allow_minor_outdated: bool,
+    /// Reasons for ignoring advisories, keyed by advisory ID
+    pub ignored_advisories: IndexMap<String, String>,
+    /// Reasons for ignoring crates entirely
+    pub ignored_crates: IndexMap<CrateName, String>
+}
+
+impl Policy {
+    pub fn is_empty(&self) -> bool {
+        !self.allow_minor_outdated && self.ignored_advisories.is_empty() && self.ignored_crates.is_empty()
+    }
+
+    pub fn advisory_ignore_reason(&self, id: &str) -> Option<&str> {
+        self.ignored_advisories.get(id).map(|reason| reason.as_str())
+    }
+
+    pub fn crate_ignore_reason(&self, name: &CrateName) -> Option<&str> {
+        self.ignored_crates.get(name).map(|reason| reason.as_str())
+    }
+}
+
+impl Hash for Policy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.allow_minor_outdated.hash(state);
+        for (id, reason) in self.ignored_advisories.iter() {
+            id.hash(state);
+            reason.hash(state);
+        }
+        self.ignored_advisories.len().hash(state);
+        for (name, reason) in self.ignored_crates.iter() {
+            name.hash(state);
+            reason.hash(state);
+        }
+        self.ignored_crates.len().hash(state);
+    }
+}
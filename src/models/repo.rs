@@ -12,17 +12,31 @@ pub struct Repository {
 pub struct RepoPath {
     pub site: RepoSite,
     pub qual: RepoQualifier,
-    pub name: RepoName
+    pub name: RepoName,
+    pub git_ref: Option<RepoRef>
 }
 
 impl RepoPath {
     pub fn from_parts(site: &str, qual: &str, name: &str) -> Result<RepoPath, Error> {
+        RepoPath::from_parts_with_ref(site, qual, name, None)
+    }
+
+    pub fn from_parts_with_ref(site: &str, qual: &str, name: &str, git_ref: Option<&str>) -> Result<RepoPath, Error> {
         Ok(RepoPath {
             site: site.parse()?,
             qual: qual.parse()?,
-            name: name.parse()?
+            name: name.parse()?,
+            git_ref: match git_ref {
+                Some(git_ref) => Some(git_ref.parse()?),
+                None => None
+            }
         })
     }
+
+    /// The branch, tag or commit to look at, defaulting to `HEAD`
+    pub fn revision(&self) -> &str {
+        self.git_ref.as_ref().map(|git_ref| git_ref.as_ref()).unwrap_or("HEAD")
+    }
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
@@ -40,6 +54,14 @@ impl RepoSite {
             &RepoSite::Bitbucket => "https://bitbucket.org",
         }
     }
+
+    pub fn to_tree_segment(&self) -> &'static str {
+        match self {
+            &RepoSite::Github => "tree",
+            &RepoSite::Gitlab => "tree",
+            &RepoSite::Bitbucket => "src",
+        }
+    }
 }
 
 impl FromStr for RepoSite {
@@ -108,3 +130,25 @@ impl AsRef<str> for RepoName {
         self.0.as_ref()
     }
 }
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RepoRef(String);
+
+impl FromStr for RepoRef {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<RepoRef, Error> {
+        let is_valid = !input.is_empty() && input.chars().all(|c| {
+            c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' || c == '/' || c == '+'
+        }) && !input.contains("..") && !input.starts_with('/') && !input.ends_with('/');
+
+        ensure!(is_valid, "invalid repo ref");
+        Ok(RepoRef(input.to_string()))
+    }
+}
+
+impl AsRef<str> for RepoRef {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use toml;
+
+use ::models::crates::{AdvisoryKind, AdvisoryMetadata, Cvss};
+
+#[derive(Deserialize, Debug)]
+struct AdvisoryToml {
+    id: String,
+    informational: Option<String>,
+    cvss: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+struct AdvisoriesToml {
+    #[serde(default)]
+    advisory: Vec<AdvisoryToml>
+}
+
+/// Parses the advisory kinds and CVSS vectors from the raw advisory database,
+/// keyed by advisory ID
+pub fn parse_advisory_metadata(input: &str) -> Result<HashMap<String, AdvisoryMetadata>, Error> {
+    let advisories = toml::from_str::<AdvisoriesToml>(input)?;
+
+    Ok(advisories.advisory.into_iter().map(|advisory| {
+        let kind = match advisory.informational.as_ref().map(|s| s.as_str()) {
+            None => AdvisoryKind::Vulnerability,
+            Some("unmaintained") => AdvisoryKind::Unmaintained,
+            Some("unsound") => AdvisoryKind::Unsound,
+            Some(_) => AdvisoryKind::Notice
+        };
+        let cvss = advisory.cvss.and_then(|vector| parse_cvss_vector(&vector));
+
+        (advisory.id, AdvisoryMetadata { kind, cvss })
+    }).collect())
+}
+
+/// Computes the base score of a CVSS v3 vector like `CVSS:3.1/AV:N/AC:L/...`
+pub fn parse_cvss_vector(vector: &str) -> Option<Cvss> {
+    let mut parts = vector.split('/');
+    match parts.next() {
+        Some("CVSS:3.0") | Some("CVSS:3.1") => (),
+        _ => return None
+    }
+
+    let metrics = parts
+        .map(|part| {
+            let mut split = part.splitn(2, ':');
+            (split.next().unwrap_or(""), split.next().unwrap_or(""))
+        })
+        .collect::<HashMap<_, _>>();
+    let metric = |name: &str| metrics.get(name).cloned();
+
+    let scope_changed = match metric("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None
+    };
+    let attack_vector = match metric("AV")? {
+        "N" => 0.85, "A" => 0.62, "L" => 0.55, "P" => 0.2,
+        _ => return None
+    };
+    let attack_complexity = match metric("AC")? {
+        "L" => 0.77, "H" => 0.44,
+        _ => return None
+    };
+    let privileges_required = match (metric("PR")?, scope_changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None
+    };
+    let user_interaction = match metric("UI")? {
+        "N" => 0.85, "R" => 0.62,
+        _ => return None
+    };
+    let impact_metric = |name: &str| match metric(name) {
+        Some("H") => Some(0.56),
+        Some("L") => Some(0.22),
+        Some("N") => Some(0.0),
+        _ => None
+    };
+    let (confidentiality, integrity, availability) = (impact_metric("C")?, impact_metric("I")?, impact_metric("A")?);
+
+    let impact_subscore = 1.0 - (1.0 - confidentiality) * (1.0 - integrity) * (1.0 - availability);
+    let impact = if scope_changed {
+        7.52 * (impact_subscore - 0.029) - 3.25 * (impact_subscore - 0.02f64).powi(15)
+    } else {
+        6.42 * impact_subscore
+    };
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges_required * user_interaction;
+
+    let base_score = if impact <= 0.0 {
+        0.0
+    } else if scope_changed {
+        round_up((1.08 * (impact + exploitability)).min(10.0))
+    } else {
+        round_up((impact + exploitability).min(10.0))
+    };
+
+    Some(Cvss { vector: vector.to_string(), base_score })
+}
+
+/// Rounds up to one decimal, as defined by CVSS v3.1
+fn round_up(value: f64) -> f64 {
+    let int_input = (value * 100_000.0).round() as u64;
+    if int_input % 10_000 == 0 {
+        int_input as f64 / 100_000.0
+    } else {
+        (int_input / 10_000 + 1) as f64 / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use models::crates::AdvisoryKind;
+    use super::{parse_advisory_metadata, parse_cvss_vector};
+
+    #[test]
+    fn parse_advisory_kinds() {
+        let toml = r#"
+[[advisory]]
+id = "RUSTSEC-2018-0001"
+package = "untrusted"
+title = "An integer underflow could lead to panic"
+cvss = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H"
+
+[[advisory]]
+id = "RUSTSEC-2020-0016"
+package = "net2"
+title = "net2 crate has been deprecated"
+informational = "unmaintained"
+"#;
+
+        let metadata = parse_advisory_metadata(toml).unwrap();
+
+        assert_eq!(metadata["RUSTSEC-2018-0001"].kind, AdvisoryKind::Vulnerability);
+        assert_eq!(metadata["RUSTSEC-2018-0001"].cvss.as_ref().map(|cvss| cvss.base_score), Some(7.5));
+        assert_eq!(metadata["RUSTSEC-2020-0016"].kind, AdvisoryKind::Unmaintained);
+        assert_eq!(metadata["RUSTSEC-2020-0016"].cvss, None);
+    }
+
+    #[test]
+    fn parse_cvss_base_scores() {
+        let score = |vector| parse_cvss_vector(vector).map(|cvss| cvss.base_score);
+
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"), Some(9.8));
+        assert_eq!(score("CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"), Some(10.0));
+        assert_eq!(score("CVSS:3.1/AV:L/AC:L/PR:L/UI:N/S:U/C:N/I:N/A:N"), Some(0.0));
+        assert_eq!(score("CVSS:2.0/AV:N"), None);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L"), None);
+    }
+}
@@ -0,0 +1,178 @@
+use failure::Error;
+
+/// Condition of a `[target.'cfg(...)'.dependencies]` table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfgExpr {
+    /// A plain name like `unix`
+    Name(String),
+    /// A key-value pair like `target_os = "linux"`
+    KeyValue(String, String),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>)
+}
+
+impl CfgExpr {
+    /// Evaluates the expression, given whether a name or key-value pair is set
+    pub fn matches<F: Fn(&str, Option<&str>) -> bool>(&self, is_set: &F) -> bool {
+        match *self {
+            CfgExpr::Name(ref name) => is_set(name, None),
+            CfgExpr::KeyValue(ref key, ref value) => is_set(key, Some(&value[..])),
+            CfgExpr::All(ref exprs) => exprs.iter().all(|expr| expr.matches(is_set)),
+            CfgExpr::Any(ref exprs) => exprs.iter().any(|expr| expr.matches(is_set)),
+            CfgExpr::Not(ref expr) => !expr.matches(is_set)
+        }
+    }
+}
+
+/// Key of a target-specific dependency table, either a target triple or a cfg expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TargetSpec {
+    Triple(String),
+    Cfg(CfgExpr)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    Equals
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {},
+            '(' => tokens.push(Token::LeftParen),
+            ')' => tokens.push(Token::RightParen),
+            ',' => tokens.push(Token::Comma),
+            '=' => tokens.push(Token::Equals),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(format_err!("unterminated string in cfg expression: {}", input))
+                    }
+                }
+                tokens.push(Token::Str(value));
+            },
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' {
+                        ident.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            },
+            _ => return Err(format_err!("unexpected character {:?} in cfg expression: {}", c, input))
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_expr(tokens: &[Token], pos: &mut usize) -> Result<CfgExpr, Error> {
+    let ident = match tokens.get(*pos) {
+        Some(&Token::Ident(ref ident)) => ident.clone(),
+        token => return Err(format_err!("expected identifier in cfg expression, found {:?}", token))
+    };
+    *pos += 1;
+
+    match tokens.get(*pos) {
+        Some(&Token::Equals) => {
+            *pos += 1;
+            match tokens.get(*pos) {
+                Some(&Token::Str(ref value)) => {
+                    *pos += 1;
+                    Ok(CfgExpr::KeyValue(ident, value.clone()))
+                },
+                token => Err(format_err!("expected string in cfg expression, found {:?}", token))
+            }
+        },
+        Some(&Token::LeftParen) => {
+            *pos += 1;
+            let mut exprs = vec![];
+            while tokens.get(*pos) != Some(&Token::RightParen) {
+                exprs.push(parse_expr(tokens, pos)?);
+                match tokens.get(*pos) {
+                    Some(&Token::Comma) => *pos += 1,
+                    Some(&Token::RightParen) => {},
+                    token => return Err(format_err!("expected `,` or `)` in cfg expression, found {:?}", token))
+                }
+            }
+            *pos += 1;
+
+            match ident.as_ref() {
+                "all" => Ok(CfgExpr::All(exprs)),
+                "any" => Ok(CfgExpr::Any(exprs)),
+                "not" if exprs.len() == 1 => Ok(CfgExpr::Not(Box::new(exprs.remove(0)))),
+                _ => Err(format_err!("invalid cfg predicate {}", ident))
+            }
+        },
+        _ => Ok(CfgExpr::Name(ident))
+    }
+}
+
+/// Parses the key of a target table, e.g. `cfg(unix)` or `x86_64-pc-windows-msvc`
+pub fn parse_target(input: &str) -> Result<TargetSpec, Error> {
+    let input = input.trim();
+
+    if input.starts_with("cfg(") && input.ends_with(')') {
+        let tokens = tokenize(&input[4..input.len() - 1])?;
+        let mut pos = 0;
+        let expr = parse_expr(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(format_err!("trailing tokens in cfg expression: {}", input));
+        }
+        Ok(TargetSpec::Cfg(expr))
+    } else {
+        Ok(TargetSpec::Triple(input.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CfgExpr, TargetSpec, parse_target};
+
+    #[test]
+    fn parse_cfg_expressions() {
+        assert_eq!(parse_target("cfg(unix)").unwrap(), TargetSpec::Cfg(CfgExpr::Name("unix".to_string())));
+        assert_eq!(parse_target("x86_64-pc-windows-msvc").unwrap(), TargetSpec::Triple("x86_64-pc-windows-msvc".to_string()));
+        assert_eq!(parse_target(r#"cfg(all(target_os = "linux", not(target_env = "musl")))"#).unwrap(),
+            TargetSpec::Cfg(CfgExpr::All(vec![
+                CfgExpr::KeyValue("target_os".to_string(), "linux".to_string()),
+                CfgExpr::Not(Box::new(CfgExpr::KeyValue("target_env".to_string(), "musl".to_string())))
+            ])));
+        assert!(parse_target("cfg(not(unix, windows))").is_err());
+        assert!(parse_target(r#"cfg(target_os = "linux)"#).is_err());
+    }
+
+    #[test]
+    fn evaluate_cfg_expressions() {
+        let is_set = |key: &str, value: Option<&str>| match (key, value) {
+            ("unix", None) | ("target_os", Some("linux")) => true,
+            _ => false
+        };
+        let matches = |input: &str| match parse_target(input).unwrap() {
+            TargetSpec::Cfg(expr) => expr.matches(&is_set),
+            TargetSpec::Triple(_) => panic!("not a cfg expression")
+        };
+
+        assert!(matches("cfg(unix)"));
+        assert!(!matches("cfg(windows)"));
+        assert!(matches(r#"cfg(any(windows, target_os = "linux"))"#));
+        assert!(!matches(r#"cfg(all(unix, not(target_os = "linux")))"#));
+    }
+}
@@ -0,0 +1,77 @@
+use failure::Error;
+use semver::Version;
+use toml;
+
+use ::models::crates::{CrateName, CrateLockfile};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CargoLockPackage {
+    name: String,
+    version: Version,
+    #[serde(default)]
+    source: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<CargoLockPackage>
+}
+
+pub fn parse_lockfile_toml(input: &str) -> Result<CrateLockfile, Error> {
+    let cargo_lock = toml::de::from_str::<CargoLock>(input)?;
+
+    let mut lockfile = CrateLockfile::default();
+
+    for package in cargo_lock.package {
+        let is_registry = package.source.as_ref()
+            .map(|source| source.starts_with("registry+"))
+            .unwrap_or(false);
+
+        if is_registry {
+            let crate_name = package.name.parse::<CrateName>()?;
+            lockfile.packages.entry(crate_name).or_insert_with(Vec::new).push(package.version);
+        }
+    }
+
+    Ok(lockfile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_lockfile_toml;
+
+    #[test]
+    fn parse_registry_packages_only() {
+        let toml = r#"[[package]]
+name = "futures"
+version = "0.1.18"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "futures"
+version = "0.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "shiny-robots"
+version = "0.1.0"
+dependencies = [
+ "futures 0.1.18 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "badge"
+version = "0.2.0"
+source = "git+https://github.com/onur/docs.rs#0123456789abcdef"
+"#;
+
+        let lockfile = parse_lockfile_toml(toml).unwrap();
+
+        assert_eq!(lockfile.packages.len(), 1);
+        assert_eq!(lockfile.packages["futures"].len(), 2);
+        assert_eq!(lockfile.find_locked(&"futures".parse().unwrap(), &"0.1".parse().unwrap()),
+            Some(&"0.1.18".parse().unwrap()));
+        assert_eq!(lockfile.find_locked(&"futures".parse().unwrap(), &"0.3".parse().unwrap()), None);
+    }
+}
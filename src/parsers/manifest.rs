@@ -10,7 +10,10 @@ use ::models::crates::{CrateName, CrateDep, CrateDeps, CrateManifest};
 struct CargoTomlComplexDependency {
     git: Option<String>,
     path: Option<RelativePathBuf>,
-    version: Option<String>
+    version: Option<String>,
+    package: Option<String>,
+    #[serde(default)]
+    workspace: bool
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,7 +31,23 @@ struct CargoTomlPackage {
 #[derive(Serialize, Deserialize, Debug)]
 struct CargoTomlWorkspace {
     #[serde(default)]
-    members: Vec<RelativePathBuf>
+    members: Vec<RelativePathBuf>,
+    #[serde(default)]
+    exclude: Vec<RelativePathBuf>,
+    #[serde(default)]
+    dependencies: IndexMap<String, CargoTomlDependency>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CargoTomlTarget {
+    #[serde(default)]
+    dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(rename = "dev-dependencies")]
+    #[serde(default)]
+    dev_dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(rename = "build-dependencies")]
+    #[serde(default)]
+    build_dependencies: IndexMap<String, CargoTomlDependency>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -44,7 +63,9 @@ struct CargoToml {
     dev_dependencies: IndexMap<String, CargoTomlDependency>,
     #[serde(rename = "build-dependencies")]
     #[serde(default)]
-    build_dependencies: IndexMap<String, CargoTomlDependency>
+    build_dependencies: IndexMap<String, CargoTomlDependency>,
+    #[serde(default)]
+    target: IndexMap<String, CargoTomlTarget>
 }
 
 fn convert_dependency(cargo_dep: (String, CargoTomlDependency)) -> Option<Result<(CrateName, CrateDep), Error>> {
@@ -56,7 +77,11 @@ fn convert_dependency(cargo_dep: (String, CargoTomlDependency)) -> Option<Result
             }))
         }
         (name, CargoTomlDependency::Complex(cplx)) => {
-            if cplx.git.is_some() {
+            if cplx.workspace {
+                Some(name.parse::<CrateName>().map_err(|err| err.into()).map(|parsed_name| {
+                    (parsed_name, CrateDep::Inherited)
+                }))
+            } else if cplx.git.is_some() {
                 None
             } else if cplx.path.is_some() {
                 cplx.path.map(|path| {
@@ -65,10 +90,14 @@ fn convert_dependency(cargo_dep: (String, CargoTomlDependency)) -> Option<Result
                     })
                 })
             } else {
+                let package = cplx.package;
                 cplx.version.map(|string| {
-                    name.parse::<CrateName>().map_err(|err| err.into()).and_then(|parsed_name| {
-                        string.parse::<VersionReq>().map_err(|err| err.into())
-                            .map(|version| (parsed_name, CrateDep::External(version)))
+                    name.parse::<CrateName>().and_then(|parsed_name| {
+                        let version = string.parse::<VersionReq>()?;
+                        match package {
+                            Some(package) => Ok((parsed_name, CrateDep::Renamed(package.parse()?, version))),
+                            None => Ok((parsed_name, CrateDep::External(version)))
+                        }
                     })
                 })
             }
@@ -92,26 +121,44 @@ pub fn parse_manifest_toml(input: &str) -> Result<CrateManifest, Error> {
         let build_dependencies = cargo_toml.build_dependencies
             .into_iter().filter_map(convert_dependency).collect::<Result<IndexMap<_, _>, _>>()?;
 
+        let mut targets = IndexMap::new();
+        for (target, target_table) in cargo_toml.target {
+            let target_deps = CrateDeps {
+                main: target_table.dependencies
+                    .into_iter().filter_map(convert_dependency).collect::<Result<IndexMap<_, _>, _>>()?,
+                dev: target_table.dev_dependencies
+                    .into_iter().filter_map(convert_dependency).collect::<Result<IndexMap<_, _>, _>>()?,
+                build: target_table.build_dependencies
+                    .into_iter().filter_map(convert_dependency).collect::<Result<IndexMap<_, _>, _>>()?,
+                targets: IndexMap::new()
+            };
+            targets.insert(target, target_deps);
+        }
+
         let deps = CrateDeps {
             main: dependencies,
             dev: dev_dependencies,
-            build: build_dependencies
+            build: build_dependencies,
+            targets
         };
 
         package_part = Some((crate_name, deps));
     }
 
     if let Some(workspace) = cargo_toml.workspace {
-        workspace_part = Some(workspace.members);
+        let dependencies = workspace.dependencies
+            .into_iter().filter_map(convert_dependency).collect::<Result<IndexMap<_, _>, _>>()?;
+
+        workspace_part = Some((workspace.members, workspace.exclude, dependencies));
     }
 
     match (package_part, workspace_part) {
         (Some((name, deps)), None) =>
             Ok(CrateManifest::Package(name, deps)),
-        (None, Some(members)) =>
-            Ok(CrateManifest::Workspace { members }),
-        (Some((name, deps)), Some(members)) =>
-            Ok(CrateManifest::Mixed { name, deps, members }),
+        (None, Some((members, exclude, dependencies))) =>
+            Ok(CrateManifest::Workspace { members, exclude, dependencies }),
+        (Some((name, deps)), Some((members, exclude, dependencies))) =>
+            Ok(CrateManifest::Mixed { name, deps, members, exclude, dependencies }),
         (None, None) =>
             Err(format_err!("neither workspace nor package found in manifest"))
     }
@@ -119,7 +166,7 @@ pub fn parse_manifest_toml(input: &str) -> Result<CrateManifest, Error> {
 
 #[cfg(test)]
 mod tests {
-    use models::crates::CrateManifest;
+    use models::crates::{CrateDep, CrateManifest};
     use super::parse_manifest_toml;
 
     #[test]
@@ -136,7 +183,7 @@ symbolic-common = { version = "2.0.6", path = "common" }
         let manifest = parse_manifest_toml(toml).unwrap();
 
         match manifest {
-            CrateManifest::Mixed { name, deps, members } => {
+            CrateManifest::Mixed { name, deps, members, .. } => {
                 assert_eq!(name.as_ref(), "symbolic");
                 assert_eq!(deps.main.len(), 1);
                 assert_eq!(deps.dev.len(), 0);
@@ -146,4 +193,82 @@ symbolic-common = { version = "2.0.6", path = "common" }
             _ => panic!("expected mixed manifest")
         }
     }
+
+    #[test]
+    fn parse_workspace_dependency_inheritance() {
+        let toml = r#"[package]
+name = "member"
+
+[dependencies]
+serde = { workspace = true, features = ["derive"] }
+log = { workspace = true }
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(name, deps) => {
+                assert_eq!(name.as_ref(), "member");
+                assert_eq!(deps.main.len(), 2);
+                assert_eq!(deps.main.get("serde").unwrap(), &CrateDep::Inherited);
+                assert_eq!(deps.main.get("log").unwrap(), &CrateDep::Inherited);
+            },
+            _ => panic!("expected package manifest")
+        }
+    }
+
+    #[test]
+    fn parse_renamed_dependency() {
+        let toml = r#"[package]
+name = "renamer"
+
+[dependencies]
+http02 = { package = "http", version = "0.2" }
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(deps.main.len(), 1);
+                assert_eq!(deps.main.get("http02").unwrap(),
+                    &CrateDep::Renamed("http".parse().unwrap(), "0.2".parse().unwrap()));
+            },
+            _ => panic!("expected package manifest")
+        }
+    }
+
+    #[test]
+    fn parse_target_specific_dependencies() {
+        let toml = r#"[package]
+name = "platform"
+
+[dependencies]
+log = "0.4"
+
+[target.'cfg(windows)'.dependencies]
+winapi = { version = "0.3", features = ["winuser"] }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[target.'cfg(unix)'.build-dependencies]
+cc = "1.0"
+"#;
+
+        let manifest = parse_manifest_toml(toml).unwrap();
+
+        match manifest {
+            CrateManifest::Package(_, deps) => {
+                assert_eq!(deps.main.len(), 1);
+                assert_eq!(deps.targets.len(), 2);
+                assert_eq!(deps.targets["cfg(windows)"].main.get("winapi").unwrap(),
+                    &CrateDep::External("0.3".parse().unwrap()));
+                assert_eq!(deps.targets["cfg(unix)"].main.len(), 1);
+                assert_eq!(deps.targets["cfg(unix)"].build.len(), 1);
+                assert_eq!(deps.iter().count(), 4);
+            },
+            _ => panic!("expected package manifest")
+        }
+    }
 }
@@ -1 +1,5 @@
 pub mod manifest;
+pub mod lockfile;
+pub mod advisories;
+pub mod policy;
+pub mod cfg;
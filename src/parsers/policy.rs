@@ -0,0 +1,136 @@
+use failure::Error;
+use indexmap::IndexMap;
+use toml;
+
+use ::models::crates::CrateName;
+use ::models::policy::Policy;
+
+const DEFAULT_REASON: &str = "no reason given";
+
+#[derive(Deserialize, Debug)]
+struct IgnoredAdvisoryToml {
+    id: String,
+    reason: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+struct IgnoredCrateToml {
+    name: String,
+    reason: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct PolicyToml {
+    #[serde(default)]
+    allow_minor_outdated: bool,
+    #[serde(default)]
+    ignore_advisory: Vec<IgnoredAdvisoryToml>,
+    #[serde(default)]
+    ignore_crate: Vec<IgnoredCrateToml>
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataToml {
+    #[serde(rename = "deps-rs")]
+    deps_rs: Option<PolicyToml>
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataSectionToml {
+    metadata: Option<MetadataToml>
+}
+
+#[derive(Deserialize, Debug)]
+struct ManifestToml {
+    package: Option<MetadataSectionToml>,
+    workspace: Option<MetadataSectionToml>
+}
+
+fn convert_policy(policy_toml: PolicyToml) -> Result<Policy, Error> {
+    let ignored_advisories = policy_toml.ignore_advisory.into_iter().map(|ignored| {
+        (ignored.id, ignored.reason.unwrap_or_else(|| DEFAULT_REASON.to_string()))
+    }).collect::<IndexMap<_, _>>();
+
+    let ignored_crates = policy_toml.ignore_crate.into_iter().map(|ignored| {
+        Ok((ignored.name.parse::<CrateName>()?, ignored.reason.unwrap_or_else(|| DEFAULT_REASON.to_string())))
+    }).collect::<Result<IndexMap<_, _>, Error>>()?;
+
+    Ok(Policy {
+        allow_minor_outdated: policy_toml.allow_minor_outdated,
+        ignored_advisories, ignored_crates
+    })
+}
+
+/// Parses a standalone `.deps.toml` policy file
+pub fn parse_policy_toml(input: &str) -> Result<Policy, Error> {
+    convert_policy(toml::de::from_str::<PolicyToml>(input)?)
+}
+
+/// Parses the policy embedded in a manifest as `[package.metadata.deps-rs]`
+/// or `[workspace.metadata.deps-rs]`, if any
+pub fn parse_manifest_policy(input: &str) -> Result<Option<Policy>, Error> {
+    let ManifestToml { package, workspace } = toml::de::from_str::<ManifestToml>(input)?;
+
+    let policy_toml = package.and_then(|package| package.metadata)
+        .and_then(|metadata| metadata.deps_rs)
+        .or_else(|| {
+            workspace.and_then(|workspace| workspace.metadata)
+                .and_then(|metadata| metadata.deps_rs)
+        });
+
+    match policy_toml {
+        Some(policy_toml) => convert_policy(policy_toml).map(Some),
+        None => Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_manifest_policy, parse_policy_toml};
+
+    #[test]
+    fn parse_policy_file() {
+        let toml = r#"
+allow-minor-outdated = true
+
+[[ignore-advisory]]
+id = "RUSTSEC-2020-0016"
+reason = "net2 is only used in tests"
+
+[[ignore-crate]]
+name = "winapi"
+"#;
+
+        let policy = parse_policy_toml(toml).unwrap();
+
+        assert!(policy.allow_minor_outdated);
+        assert_eq!(policy.advisory_ignore_reason("RUSTSEC-2020-0016"), Some("net2 is only used in tests"));
+        assert_eq!(policy.crate_ignore_reason(&"winapi".parse().unwrap()), Some("no reason given"));
+        assert_eq!(policy.crate_ignore_reason(&"hyper".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn parse_policy_from_manifest() {
+        let toml = r#"
+[package]
+name = "example"
+
+[package.metadata.deps-rs]
+allow-minor-outdated = true
+
+[dependencies]
+hyper = "0.11"
+"#;
+
+        let policy = parse_manifest_policy(toml).unwrap().unwrap();
+        assert!(policy.allow_minor_outdated);
+
+        let toml = r#"
+[package]
+name = "example"
+"#;
+
+        assert!(parse_manifest_policy(toml).unwrap().is_none());
+    }
+}
@@ -1,18 +1,23 @@
+use std::collections::hash_map::DefaultHasher;
 use std::env;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 use futures::{Future, IntoFuture, future};
 use hyper::{Error as HyperError, Method, Request, Response, StatusCode};
-use hyper::header::{ContentType, Location};
+use hyper::header::{CacheControl, CacheDirective, ContentType, ETag, EntityTag, IfNoneMatch, Location};
 use route_recognizer::{Params, Router};
 use semver::VersionReq;
 use slog::Logger;
 use tokio_service::Service;
+use url::form_urlencoded;
+use url::percent_encoding::percent_decode;
 
 mod assets;
 mod views;
 
 use ::engine::{Engine, AnalyzeDependenciesOutcome};
+use ::interactors::{UpstreamError, UpstreamErrorKind};
 use ::models::crates::{CrateName, CratePath};
 use ::models::repo::RepoPath;
 use ::models::SubjectPath;
@@ -20,7 +25,86 @@ use ::models::SubjectPath;
 #[derive(Clone, Copy, PartialEq)]
 enum StatusFormat {
     Html,
-    Svg
+    Svg,
+    Png,
+    Json,
+    Shields
+}
+
+impl StatusFormat {
+    fn max_age(&self) -> u32 {
+        match *self {
+            StatusFormat::Html => *HTML_MAX_AGE,
+            StatusFormat::Svg | StatusFormat::Png | StatusFormat::Shields => *SVG_MAX_AGE,
+            StatusFormat::Json => *JSON_MAX_AGE
+        }
+    }
+}
+
+/// Parts of a status request that affect how the response is rendered and cached
+#[derive(Clone)]
+struct StatusRequest {
+    query: Option<String>,
+    if_none_match: Option<IfNoneMatch>
+}
+
+impl StatusRequest {
+    fn from_request(req: &Request) -> StatusRequest {
+        StatusRequest {
+            query: req.query().map(|query| query.to_string()),
+            if_none_match: req.headers().get::<IfNoneMatch>().cloned()
+        }
+    }
+
+    fn entity_tag(&self, analysis_outcome: &AnalyzeDependenciesOutcome) -> EntityTag {
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        self.query.hash(&mut hasher);
+        analysis_outcome.fingerprint().hash(&mut hasher);
+        EntityTag::strong(format!("{:016x}", hasher.finish()))
+    }
+
+    fn query_param(&self, key: &str) -> Option<String> {
+        find_query_param(self.query.as_ref().map(|query| query.as_str()), key)
+    }
+
+    /// Badge customizations requested via the query string, ignoring invalid values
+    fn badge_customization(&self) -> views::badge::BadgeCustomization {
+        let color_param = |key: &str| self.query_param(key).and_then(|color| views::badge::parse_color(&color));
+
+        views::badge::BadgeCustomization {
+            style: self.query_param("style")
+                .and_then(|style| style.parse().ok())
+                .unwrap_or_default(),
+            mode: self.query_param("mode")
+                .and_then(|mode| mode.parse().ok())
+                .unwrap_or_default(),
+            label: self.query_param("label")
+                .and_then(|label| views::badge::parse_label(&label)),
+            compact: self.query_param("compact")
+                .map(|compact| compact == "true" || compact == "1")
+                .unwrap_or(false),
+            label_color: color_param("labelColor"),
+            up_to_date_color: color_param("upToDateColor"),
+            outdated_color: color_param("outdatedColor"),
+            insecure_color: color_param("insecureColor")
+        }
+    }
+
+    /// Scale factor for PNG badges, e.g. `2` for HiDPI displays
+    fn badge_scale(&self) -> u32 {
+        self.query_param("scale")
+            .and_then(|scale| scale.parse().ok())
+            .unwrap_or(1)
+    }
+
+    fn is_fresh(&self, etag: &EntityTag) -> bool {
+        match self.if_none_match {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(ref tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+            None => false
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -55,10 +139,21 @@ impl Server {
 
         router.add("/repo/:site/:qual/:name", Route::RepoStatus(StatusFormat::Html));
         router.add("/repo/:site/:qual/:name/status.svg", Route::RepoStatus(StatusFormat::Svg));
+        router.add("/repo/:site/:qual/:name/status.png", Route::RepoStatus(StatusFormat::Png));
+        router.add("/repo/:site/:qual/:name/status.json", Route::RepoStatus(StatusFormat::Json));
+        router.add("/repo/:site/:qual/:name/shields.json", Route::RepoStatus(StatusFormat::Shields));
+        router.add("/repo/:site/:qual/:name/tree/:ref", Route::RepoStatus(StatusFormat::Html));
+        router.add("/repo/:site/:qual/:name/tree/:ref/status.svg", Route::RepoStatus(StatusFormat::Svg));
+        router.add("/repo/:site/:qual/:name/tree/:ref/status.png", Route::RepoStatus(StatusFormat::Png));
+        router.add("/repo/:site/:qual/:name/tree/:ref/status.json", Route::RepoStatus(StatusFormat::Json));
+        router.add("/repo/:site/:qual/:name/tree/:ref/shields.json", Route::RepoStatus(StatusFormat::Shields));
 
         router.add("/crate/:name", Route::CrateRedirect);
         router.add("/crate/:name/:version", Route::CrateStatus(StatusFormat::Html));
         router.add("/crate/:name/:version/status.svg", Route::CrateStatus(StatusFormat::Svg));
+        router.add("/crate/:name/:version/status.png", Route::CrateStatus(StatusFormat::Png));
+        router.add("/crate/:name/:version/status.json", Route::CrateStatus(StatusFormat::Json));
+        router.add("/crate/:name/:version/shields.json", Route::CrateStatus(StatusFormat::Shields));
 
         Server { logger, engine, router: Arc::new(router) }
     }
@@ -130,7 +225,7 @@ impl Server {
             })
     }
 
-    fn repo_status(&self, _req: Request, params: Params, logger: Logger, format: StatusFormat) ->
+    fn repo_status(&self, req: Request, params: Params, logger: Logger, format: StatusFormat) ->
         impl Future<Item=Response, Error=HyperError>
     {
         let server = self.clone();
@@ -139,7 +234,14 @@ impl Server {
         let qual = params.find("qual").expect("route param 'qual' not found");
         let name = params.find("name").expect("route param 'name' not found");
 
-        RepoPath::from_parts(site, qual, name).into_future().then(move |repo_path_result| {
+        // refs containing slashes need to be percent-encoded in the path
+        let git_ref = params.find("ref")
+            .map(|git_ref| percent_decode(git_ref.as_bytes()).decode_utf8_lossy().into_owned())
+            .or_else(|| Server::query_param(&req, "ref"));
+
+        let status_request = StatusRequest::from_request(&req);
+
+        RepoPath::from_parts_with_ref(site, qual, name, git_ref.as_ref().map(|s| s.as_str())).into_future().then(move |repo_path_result| {
             match repo_path_result {
                 Err(err) => {
                     error!(logger, "error: {}", err);
@@ -153,11 +255,12 @@ impl Server {
                         match analyze_result {
                             Err(err) => {
                                 error!(logger, "error: {}", err);
-                                let response = Server::status_format_analysis(None, format, SubjectPath::Repo(repo_path));
+                                let kind = UpstreamError::classify(&err);
+                                let response = Server::status_format_analysis(Err(kind), format, SubjectPath::Repo(repo_path), &status_request);
                                 future::ok(response)
                             },
                             Ok(analysis_outcome) => {
-                                let response = Server::status_format_analysis(Some(analysis_outcome), format, SubjectPath::Repo(repo_path));
+                                let response = Server::status_format_analysis(Ok(analysis_outcome), format, SubjectPath::Repo(repo_path), &status_request);
                                 future::ok(response)
                             }
                         }
@@ -216,11 +319,13 @@ impl Server {
         })
     }
 
-    fn crate_status(&self, _req: Request, params: Params, logger: Logger, format: StatusFormat) ->
+    fn crate_status(&self, req: Request, params: Params, logger: Logger, format: StatusFormat) ->
         impl Future<Item=Response, Error=HyperError>
     {
         let server = self.clone();
 
+        let status_request = StatusRequest::from_request(&req);
+
         let name = params.find("name").expect("route param 'name' not found");
         let version = params.find("version").expect("route param 'version' not found");
 
@@ -238,11 +343,12 @@ impl Server {
                         match analyze_result {
                             Err(err) => {
                                 error!(logger, "error: {}", err);
-                                let response = Server::status_format_analysis(None, format, SubjectPath::Crate(crate_path));
+                                let kind = UpstreamError::classify(&err);
+                                let response = Server::status_format_analysis(Err(kind), format, SubjectPath::Crate(crate_path), &status_request);
                                 future::ok(response)
                             },
                             Ok(analysis_outcome) => {
-                                let response = Server::status_format_analysis(Some(analysis_outcome), format, SubjectPath::Crate(crate_path));
+                                let response = Server::status_format_analysis(Ok(analysis_outcome), format, SubjectPath::Crate(crate_path), &status_request);
                                 future::ok(response)
                             }
                         }
@@ -252,13 +358,55 @@ impl Server {
         })
     }
 
-    fn status_format_analysis(analysis_outcome: Option<AnalyzeDependenciesOutcome>, format: StatusFormat, subject_path: SubjectPath) -> Response {
-        match format {
+    fn status_format_analysis(analysis_result: Result<AnalyzeDependenciesOutcome, UpstreamErrorKind>, format: StatusFormat, subject_path: SubjectPath, status_request: &StatusRequest) -> Response {
+        let etag = analysis_result.as_ref().ok().map(|outcome| status_request.entity_tag(outcome));
+        let max_age = if analysis_result.is_ok() {
+            format.max_age()
+        } else {
+            format.max_age().min(*FAILURE_MAX_AGE)
+        };
+
+        if let Some(ref etag) = etag {
+            if status_request.is_fresh(etag) {
+                let mut response = Response::new();
+                response.set_status(StatusCode::NotModified);
+                response.headers_mut().set(ETag(etag.clone()));
+                response.headers_mut().set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(max_age)]));
+                return response;
+            }
+        }
+
+        let status = match analysis_result {
+            // shields.io only renders endpoint badges served with a success status
+            _ if format == StatusFormat::Shields => StatusCode::Ok,
+            Ok(_) => StatusCode::Ok,
+            Err(UpstreamErrorKind::NotFound) => StatusCode::NotFound,
+            Err(UpstreamErrorKind::RateLimited) | Err(UpstreamErrorKind::Transient) => StatusCode::ServiceUnavailable
+        };
+
+        let mut response = match format {
             StatusFormat::Svg =>
-                views::badge::response(analysis_outcome.as_ref()),
+                views::badge::response(analysis_result.as_ref().map_err(|kind| *kind), &status_request.badge_customization()),
+            StatusFormat::Png =>
+                views::badge::png_response(analysis_result.as_ref().map_err(|kind| *kind), &status_request.badge_customization(),
+                    status_request.badge_scale()),
+            StatusFormat::Json =>
+                views::json::response(analysis_result.as_ref().map_err(|kind| *kind), &subject_path),
+            StatusFormat::Shields =>
+                views::badge::shields_response(analysis_result.as_ref().map_err(|kind| *kind), &status_request.badge_customization()),
             StatusFormat::Html =>
-                views::html::status::render(analysis_outcome, subject_path)
+                views::html::status::render(analysis_result, subject_path)
+        };
+        response.set_status(status);
+        response.headers_mut().set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(max_age)]));
+        if let Some(etag) = etag {
+            response.headers_mut().set(ETag(etag));
         }
+        response
+    }
+
+    fn query_param(req: &Request, key: &str) -> Option<String> {
+        find_query_param(req.query(), key)
     }
 
     fn static_file(file: StaticFile) -> Response {
@@ -277,9 +425,138 @@ impl Server {
     }
 }
 
+fn find_query_param(query: Option<&str>, key: &str) -> Option<String> {
+    query.and_then(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|&(ref k, _)| &**k == key)
+            .map(|(_, v)| v.into_owned())
+    })
+}
+
+fn max_age_from_env(key: &str, default: u32) -> u32 {
+    env::var(key).ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
 lazy_static! {
     static ref SELF_BASE_URL: String = {
         env::var("BASE_URL")
             .unwrap_or_else(|_| "http://localhost:8080".to_string())
     };
+
+    static ref HTML_MAX_AGE: u32 = max_age_from_env("HTML_MAX_AGE", 60);
+    static ref SVG_MAX_AGE: u32 = max_age_from_env("SVG_MAX_AGE", 300);
+    static ref JSON_MAX_AGE: u32 = max_age_from_env("JSON_MAX_AGE", 60);
+    static ref FAILURE_MAX_AGE: u32 = max_age_from_env("FAILURE_MAX_AGE", 30);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::{Future, Stream};
+    use hyper::{Response, StatusCode};
+    use hyper::header::{CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch};
+
+    use engine::AnalyzeDependenciesOutcome;
+    use interactors::UpstreamErrorKind;
+    use models::SubjectPath;
+    use models::crates::CratePath;
+    use models::policy::Policy;
+    use super::{FAILURE_MAX_AGE, JSON_MAX_AGE, Server, StatusFormat, StatusRequest};
+
+    fn outcome(policy: Policy, duration_ms: u64) -> AnalyzeDependenciesOutcome {
+        AnalyzeDependenciesOutcome {
+            crates: vec![],
+            policy: Arc::new(policy),
+            policy_error: None,
+            duration: Duration::from_millis(duration_ms)
+        }
+    }
+
+    fn status_request(query: Option<&str>, if_none_match: Option<IfNoneMatch>) -> StatusRequest {
+        StatusRequest {
+            query: query.map(|query| query.to_string()),
+            if_none_match
+        }
+    }
+
+    fn json_response(analysis_result: Result<AnalyzeDependenciesOutcome, UpstreamErrorKind>, status_request: &StatusRequest) -> Response {
+        let subject_path = SubjectPath::Crate(CratePath::from_parts("serde", "1.0.0").unwrap());
+        Server::status_format_analysis(analysis_result, StatusFormat::Json, subject_path, status_request)
+    }
+
+    fn body_len(response: Response) -> usize {
+        response.body().concat2().wait().unwrap().len()
+    }
+
+    #[test]
+    fn entity_tags_depend_on_query_and_results_only() {
+        let plain = status_request(None, None);
+        let styled = status_request(Some("style=flat-square"), None);
+        let tag = plain.entity_tag(&outcome(Policy::default(), 5));
+
+        assert_eq!(plain.entity_tag(&outcome(Policy::default(), 500)), tag);
+        assert!(styled.entity_tag(&outcome(Policy::default(), 5)) != tag);
+
+        let mut policy = Policy::default();
+        policy.allow_minor_outdated = true;
+        assert!(plain.entity_tag(&outcome(policy, 5)) != tag);
+
+        let mut policy = Policy::default();
+        policy.ignored_crates.insert("serde".parse().unwrap(), "vendored".to_string());
+        assert!(plain.entity_tag(&outcome(policy, 5)) != tag);
+    }
+
+    #[test]
+    fn serves_analyses_with_entity_tag() {
+        let status_request = status_request(None, None);
+        let etag = status_request.entity_tag(&outcome(Policy::default(), 5));
+        let response = json_response(Ok(outcome(Policy::default(), 5)), &status_request);
+
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.headers().get::<ETag>(), Some(&ETag(etag)));
+        assert_eq!(response.headers().get::<CacheControl>(),
+            Some(&CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(*JSON_MAX_AGE)])));
+        assert!(body_len(response) > 0);
+    }
+
+    #[test]
+    fn answers_matching_if_none_match_with_not_modified() {
+        let etag = status_request(None, None).entity_tag(&outcome(Policy::default(), 5));
+
+        for if_none_match in vec![IfNoneMatch::Items(vec![etag.clone()]), IfNoneMatch::Any] {
+            let status_request = status_request(None, Some(if_none_match));
+            let response = json_response(Ok(outcome(Policy::default(), 5)), &status_request);
+
+            assert_eq!(response.status(), StatusCode::NotModified);
+            assert_eq!(response.headers().get::<ETag>(), Some(&ETag(etag.clone())));
+            assert_eq!(response.headers().get::<CacheControl>(),
+                Some(&CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(*JSON_MAX_AGE)])));
+            assert_eq!(body_len(response), 0);
+        }
+    }
+
+    #[test]
+    fn serves_changed_analyses_in_full() {
+        let stale_tag = EntityTag::strong("0000000000000000".to_string());
+        let status_request = status_request(None, Some(IfNoneMatch::Items(vec![stale_tag])));
+        let response = json_response(Ok(outcome(Policy::default(), 5)), &status_request);
+
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(body_len(response) > 0);
+    }
+
+    #[test]
+    fn caches_failures_briefly_and_without_entity_tag() {
+        let status_request = status_request(None, Some(IfNoneMatch::Any));
+        let response = json_response(Err(UpstreamErrorKind::Transient), &status_request);
+
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(response.headers().get::<ETag>(), None);
+        assert_eq!(response.headers().get::<CacheControl>(),
+            Some(&CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge((*JSON_MAX_AGE).min(*FAILURE_MAX_AGE))])));
+    }
 }
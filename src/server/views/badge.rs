@@ -1,56 +1,298 @@
-use badge::{Badge, BadgeOptions};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use badge::{Badge, BadgeOptions, BadgeStyle};
 use hyper::Response;
 use hyper::header::ContentType;
+use serde_json;
 
 use ::engine::AnalyzeDependenciesOutcome;
+use ::interactors::UpstreamErrorKind;
+use ::models::crates::AdvisoryKind;
 
-pub fn badge(analysis_outcome: Option<&AnalyzeDependenciesOutcome>) -> Badge {
-    let opts = match analysis_outcome {
-        Some(outcome) => {
-            if outcome.any_insecure() {
-                BadgeOptions {
-                    subject: "dependencies".into(),
-                    status: "insecure".into(),
-                    color: "#e05d44".into()
-                }
-            } else {
-                let (outdated, total) = outcome.outdated_ratio();
-
-                if outdated > 0 {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: format!("{} of {} outdated", outdated, total),
-                        color: "#dfb317".into()
-                    }
-                } else if total > 0 {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: "up to date".into(),
-                        color: "#4c1".into()
-                    }
-                } else {
-                    BadgeOptions {
-                        subject: "dependencies".into(),
-                        status: "none".into(),
-                        color: "#4c1".into()
-                    }
-                }
-            }
+const MAX_LABEL_LENGTH: usize = 32;
+
+/// Which dependencies the badge counts as outdated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BadgeMode {
+    Outdated,
+    /// Only count dependencies that trail by a breaking release
+    MajorOutdated,
+    /// Show the total time the dependencies trail their latest releases by
+    Libyear
+}
+
+impl Default for BadgeMode {
+    fn default() -> BadgeMode {
+        BadgeMode::Outdated
+    }
+}
+
+impl FromStr for BadgeMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<BadgeMode, String> {
+        match input {
+            "outdated" => Ok(BadgeMode::Outdated),
+            "major" => Ok(BadgeMode::MajorOutdated),
+            "libyear" => Ok(BadgeMode::Libyear),
+            _ => Err(format!("unknown badge mode: {}", input))
+        }
+    }
+}
+
+/// Badge customizations requested by the client
+#[derive(Clone, Debug, Default)]
+pub struct BadgeCustomization {
+    pub style: BadgeStyle,
+    pub mode: BadgeMode,
+    pub label: Option<String>,
+    /// Leave out the total, e.g. "3 outdated" instead of "3 of 12 outdated"
+    pub compact: bool,
+    pub label_color: Option<String>,
+    pub up_to_date_color: Option<String>,
+    pub outdated_color: Option<String>,
+    pub insecure_color: Option<String>
+}
+
+/// Accepts labels of printable characters only, escaping is left to the badge renderer
+pub fn parse_label(input: &str) -> Option<String> {
+    let label = input.trim();
+    let is_valid = !label.is_empty()
+        && label.chars().count() <= MAX_LABEL_LENGTH
+        && !label.chars().any(|c| c.is_control());
+
+    if is_valid {
+        Some(label.to_string())
+    } else {
+        None
+    }
+}
+
+/// Accepts hex colors (with or without leading `#`) and the named colors known from shields.io
+pub fn parse_color(input: &str) -> Option<String> {
+    let named = match input {
+        "brightgreen" => Some("#4c1"),
+        "green" => Some("#97ca00"),
+        "yellowgreen" => Some("#a4a61d"),
+        "yellow" => Some("#dfb317"),
+        "orange" => Some("#fe7d37"),
+        "red" => Some("#e05d44"),
+        "blue" => Some("#007ec6"),
+        "grey" | "gray" => Some("#555"),
+        "lightgrey" | "lightgray" => Some("#9f9f9f"),
+        _ => None
+    };
+
+    if let Some(color) = named {
+        return Some(color.to_string());
+    }
+
+    let hex = input.trim_start_matches('#');
+    let is_hex = (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit());
+
+    if is_hex {
+        Some(format!("#{}", hex))
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BadgeState {
+    UpToDate,
+    Outdated,
+    Unmaintained,
+    Unsound,
+    Yanked,
+    Unsatisfiable,
+    Insecure,
+    Unknown
+}
+
+/// Dependencies trailing by a libyear or more in total count as outdated
+fn libyear_status(outcome: &AnalyzeDependenciesOutcome) -> (String, BadgeState) {
+    let libyears = outcome.libyears();
+    let state = if libyears < 1.0 { BadgeState::UpToDate } else { BadgeState::Outdated };
+
+    (format!("{:.1} libyears", libyears), state)
+}
+
+fn outdated_status(outcome: &AnalyzeDependenciesOutcome, customization: &BadgeCustomization) -> (String, BadgeState) {
+    let ((outdated, total), qualifier) = match customization.mode {
+        BadgeMode::Outdated => (outcome.outdated_ratio(), ""),
+        BadgeMode::MajorOutdated => (outcome.major_outdated_ratio(), "major "),
+        BadgeMode::Libyear => return libyear_status(outcome)
+    };
+
+    if outdated > 0 {
+        if customization.compact {
+            (format!("{} {}outdated", outdated, qualifier), BadgeState::Outdated)
+        } else {
+            (format!("{} of {} {}outdated", outdated, total, qualifier), BadgeState::Outdated)
+        }
+    } else if total > 0 {
+        ("up to date".into(), BadgeState::UpToDate)
+    } else {
+        ("none".into(), BadgeState::UpToDate)
+    }
+}
+
+fn status(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization) -> (String, BadgeState) {
+    match analysis_result {
+        Ok(outcome) => match outcome.advisory_kind() {
+            Some(AdvisoryKind::Vulnerability) => ("insecure".into(), BadgeState::Insecure),
+            Some(AdvisoryKind::Unsound) => ("unsound".into(), BadgeState::Unsound),
+            _ if outcome.any_unsatisfiable() => ("unsatisfiable".into(), BadgeState::Unsatisfiable),
+            _ if outcome.any_yanked_only() => ("yanked".into(), BadgeState::Yanked),
+            Some(AdvisoryKind::Unmaintained) => ("unmaintained".into(), BadgeState::Unmaintained),
+            // informational notices don't affect the badge
+            Some(AdvisoryKind::Notice) | None => outdated_status(outcome, customization)
         },
-        None => {
-            BadgeOptions {
-                subject: "dependencies".into(),
-                status: "unknown".into(),
-                color: "#9f9f9f".into()
+        Err(UpstreamErrorKind::NotFound) => ("not found".into(), BadgeState::Unknown),
+        Err(UpstreamErrorKind::RateLimited) => ("rate limited".into(), BadgeState::Unknown),
+        Err(UpstreamErrorKind::Transient) => ("unknown".into(), BadgeState::Unknown)
+    }
+}
+
+fn color(state: BadgeState, customization: &BadgeCustomization) -> String {
+    let (custom_color, default_color) = match state {
+        BadgeState::UpToDate => (customization.up_to_date_color.as_ref(), "#4c1"),
+        BadgeState::Outdated | BadgeState::Unmaintained => (customization.outdated_color.as_ref(), "#dfb317"),
+        BadgeState::Unsound | BadgeState::Yanked => (None, "#fe7d37"),
+        BadgeState::Unsatisfiable => (None, "#e05d44"),
+        BadgeState::Insecure => (customization.insecure_color.as_ref(), "#e05d44"),
+        BadgeState::Unknown => (None, "#9f9f9f")
+    };
+
+    custom_color.cloned().unwrap_or_else(|| default_color.to_string())
+}
+
+/// Names of the outdated and insecure crates, for assistive technology
+fn description(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>) -> Option<String> {
+    let outcome = analysis_result.ok()?;
+
+    let mut outdated = BTreeSet::new();
+    let mut insecure = BTreeSet::new();
+    for &(_, ref deps) in outcome.crates.iter() {
+        for (name, dep) in deps.iter() {
+            let package = dep.package_name(name).as_ref();
+            if dep.is_insecure() {
+                insecure.insert(package);
+            } else if dep.is_outdated() {
+                outdated.insert(package);
             }
         }
+        for dep in deps.transitive.iter().filter(|dep| dep.is_insecure()) {
+            insecure.insert(dep.name.as_ref());
+        }
+    }
+
+    let mut parts = vec![];
+    if !insecure.is_empty() {
+        parts.push(format!("Insecure: {}.", insecure.into_iter().collect::<Vec<_>>().join(", ")));
+    }
+    if !outdated.is_empty() {
+        parts.push(format!("Outdated: {}.", outdated.into_iter().collect::<Vec<_>>().join(", ")));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn options(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization) -> BadgeOptions {
+    let (status, state) = status(analysis_result, customization);
+
+    BadgeOptions {
+        subject: customization.label.clone().unwrap_or_else(|| "dependencies".to_string()),
+        status,
+        color: color(state, customization),
+        subject_color: customization.label_color.clone().unwrap_or_else(|| "#555".to_string()),
+        style: customization.style,
+        description: description(analysis_result)
+    }
+}
+
+pub fn badge(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization) -> Badge {
+    Badge::new(options(analysis_result, customization))
+}
+
+/// Response body for the shields.io endpoint badge,
+/// see https://shields.io/endpoint
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShieldsEndpointJson {
+    schema_version: u8,
+    label: String,
+    message: String,
+    color: String,
+    label_color: String,
+    is_error: bool
+}
+
+pub fn shields_response(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization) -> Response {
+    let opts = options(analysis_result, customization);
+
+    // shields.io expects hex colors without the leading `#`
+    let endpoint = ShieldsEndpointJson {
+        schema_version: 1,
+        label: opts.subject,
+        message: opts.status,
+        color: opts.color.trim_start_matches('#').to_string(),
+        label_color: opts.subject_color.trim_start_matches('#').to_string(),
+        is_error: analysis_result.is_err()
     };
 
-    Badge::new(opts)
+    let body = serde_json::to_vec(&endpoint)
+        .expect("failed to serialize shields endpoint");
+
+    Response::new()
+        .with_header(ContentType::json())
+        .with_body(body)
 }
 
-pub fn response(analysis_outcome: Option<&AnalyzeDependenciesOutcome>) -> Response {
+pub fn response(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization) -> Response {
     Response::new()
         .with_header(ContentType("image/svg+xml;charset=utf-8".parse().unwrap()))
-        .with_body(badge(analysis_outcome).to_svg().into_bytes())
+        .with_body(badge(analysis_result, customization).to_svg().into_bytes())
+}
+
+pub fn png_response(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, customization: &BadgeCustomization, scale: u32) -> Response {
+    Response::new()
+        .with_header(ContentType("image/png".parse().unwrap()))
+        .with_body(badge(analysis_result, customization).to_png(scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BadgeMode, parse_color, parse_label};
+
+    #[test]
+    fn accepts_only_safe_colors() {
+        assert_eq!(parse_color("brightgreen"), Some("#4c1".to_string()));
+        assert_eq!(parse_color("ff69b4"), Some("#ff69b4".to_string()));
+        assert_eq!(parse_color("#abc"), Some("#abc".to_string()));
+        assert_eq!(parse_color("#abcd"), None);
+        assert_eq!(parse_color("red\" onload=\"alert(1)"), None);
+        assert_eq!(parse_color("url(#evil)"), None);
+    }
+
+    #[test]
+    fn accepts_only_reasonable_labels() {
+        assert_eq!(parse_label(" deps "), Some("deps".to_string()));
+        assert_eq!(parse_label(""), None);
+        assert_eq!(parse_label("line\nbreak"), None);
+        assert_eq!(parse_label(&"x".repeat(100)), None);
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("major".parse(), Ok(BadgeMode::MajorOutdated));
+        assert_eq!("outdated".parse(), Ok(BadgeMode::Outdated));
+        assert_eq!("libyear".parse(), Ok(BadgeMode::Libyear));
+        assert!("minor".parse::<BadgeMode>().is_err());
+    }
 }
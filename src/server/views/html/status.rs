@@ -1,22 +1,34 @@
 use hyper::Response;
 use maud::{Markup, html};
 use indexmap::IndexMap;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
 
 use ::engine::AnalyzeDependenciesOutcome;
-use ::models::crates::{CrateName, AnalyzedDependency, AnalyzedDependencies};
+use ::interactors::UpstreamErrorKind;
+use ::models::crates::{AdvisoryKind, CrateName, AnalyzedDependency, AnalyzedDependencies, Lag, LagKind, TransitiveDependency};
+use ::models::policy::Policy;
 use ::models::SubjectPath;
-use ::models::repo::RepoSite;
+use ::models::repo::{RepoSite, RepoPath};
 
 use super::super::badge;
 
 fn dependency_tables(crate_name: CrateName, deps: AnalyzedDependencies) -> Markup {
+    let no_deps = deps.count_total() == 0;
+    let libyears = deps.libyears();
+
     html! {
         h2 class="title is-3" {
             "Crate "
             code {(crate_name.as_ref())}
         }
 
-        @if deps.main.is_empty() && deps.dev.is_empty() && deps.build.is_empty() {
+        @if libyears > 0.0 {
+            p class="subtitle is-6" {
+                (format!("Dependencies trail their latest releases by {:.1} libyears in total", libyears))
+            }
+        }
+
+        @if no_deps {
             p class="notification has-text-centered" {"No external dependencies! 🙌"}
         }
 
@@ -31,13 +43,160 @@ fn dependency_tables(crate_name: CrateName, deps: AnalyzedDependencies) -> Marku
         @if !deps.build.is_empty() {
             (dependency_table("Build dependencies", deps.build))
         }
+
+        @for (target, target_deps) in deps.targets {
+            @if !target_deps.main.is_empty() {
+                (dependency_table(&format!("Dependencies for {}", target), target_deps.main))
+            }
+
+            @if !target_deps.dev.is_empty() {
+                (dependency_table(&format!("Dev dependencies for {}", target), target_deps.dev))
+            }
+
+            @if !target_deps.build.is_empty() {
+                (dependency_table(&format!("Build dependencies for {}", target), target_deps.build))
+            }
+        }
+
+        @if !deps.transitive.is_empty() {
+            (transitive_table(&crate_name, deps.transitive))
+        }
+
+        @if deps.transitive_truncated {
+            p class="notification is-size-7" {
+                "The dependency tree is too large to be checked completely, only part of the transitive dependencies were checked."
+            }
+        }
+    }
+}
+
+fn transitive_table(crate_name: &CrateName, deps: Vec<TransitiveDependency>) -> Markup {
+    html! {
+        h3 class="title is-4" {"Transitive dependencies"}
+        p class="subtitle is-5" {
+            (format!(" ({} with advisories or yanked)", deps.len()))
+        }
+
+        table class="table is-fullwidth is-striped is-hoverable" {
+            thead {
+                tr {
+                    th {"Crate"}
+                    th {"Path"}
+                    th class="has-text-right" {"Version"}
+                    th class="has-text-right" {"Status"}
+                }
+            }
+            tbody {
+                @for dep in deps {
+                    tr {
+                        td {
+                            a href=(format!("https://crates.io/crates/{}", dep.name.as_ref())) {(dep.name.as_ref())}
+                        }
+                        td class="is-size-7" {
+                            (crate_name.as_ref())
+                            @for parent in dep.path {
+                                " → " (parent.as_ref())
+                            }
+                        }
+                        td class="has-text-right" {code {(dep.version.to_string())}}
+                        td class="has-text-right" {
+                            @if let Some(kind) = dep.advisory_kind {
+                                (advisory_tag(kind))
+                            }
+                            @if dep.yanked {
+                                span class="tag is-warning" {"yanked"}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn advisory_tag(kind: AdvisoryKind) -> Markup {
+    html! {
+        @match kind {
+            AdvisoryKind::Vulnerability => { span class="tag is-danger" {"insecure"} },
+            AdvisoryKind::Unsound => { span class="tag is-warning" {"unsound"} },
+            AdvisoryKind::Unmaintained => { span class="tag is-light" {"unmaintained"} },
+            AdvisoryKind::Notice => { span class="tag is-info" {"notice"} }
+        }
+    }
+}
+
+fn lag_details(lag: Option<&Lag>, libyears: Option<f64>) -> Markup {
+    let class = match lag.map(|lag| lag.kind) {
+        Some(LagKind::Major) => "has-text-danger",
+        Some(LagKind::Minor) => "has-text-warning",
+        Some(LagKind::Patch) | None => "has-text-grey"
+    };
+    let mut parts = vec![];
+    if let Some(lag) = lag {
+        parts.push(format!("{} behind, {} {}", lag.kind.as_str(), lag.releases,
+            if lag.releases == 1 { "release" } else { "releases" }));
+    }
+    match libyears {
+        Some(libyears) if libyears > 0.0 => parts.push(format!("{:.1} libyears", libyears)),
+        _ => {}
+    }
+
+    html! {
+        @if !parts.is_empty() {
+            br;
+            span class=(format!("is-size-7 {}", class)) {(parts.join(", "))}
+        }
+    }
+}
+
+fn advisory_details(dep: &AnalyzedDependency) -> Markup {
+    html! {
+        details {
+            summary class=(if dep.is_insecure() { "has-text-danger" } else { "has-text-grey" }) {
+                (format!("{} security {}", dep.advisories.len(),
+                    if dep.advisories.len() == 1 { "advisory" } else { "advisories" }))
+            }
+            ul {
+                @for advisory in dep.advisories.iter() {
+                    li {
+                        @if let Some(ref url) = advisory.url {
+                            a href=(url) {(advisory.id)}
+                        } @else {
+                            (advisory.id)
+                        }
+                        ": " (advisory.title)
+                        @if advisory.kind != AdvisoryKind::Vulnerability {
+                            " " (advisory_tag(advisory.kind))
+                        }
+                        @if let Some(ref reason) = advisory.ignored {
+                            " "
+                            span class="tag is-light" title=(reason) {"ignored"}
+                        }
+                        @if let Some(ref cvss) = advisory.cvss {
+                            " "
+                            span class="tag" title=(cvss.vector) {(format!("CVSS {:.1}", cvss.base_score))}
+                        }
+                    }
+                }
+            }
+            @if let Some(ref safe_version) = dep.safe_version {
+                p {
+                    "Upgrade to "
+                    code {(safe_version.to_string())}
+                    " or later to resolve all advisories."
+                }
+            } @else {
+                p {"No release resolves all advisories yet."}
+            }
+        }
     }
 }
 
 fn dependency_table(title: &str, deps: IndexMap<CrateName, AnalyzedDependency>) -> Markup {
     let count_total = deps.len();
-    let count_insecure = deps.iter().filter(|&(_, dep)| dep.insecure).count();
+    let count_insecure = deps.iter().filter(|&(_, dep)| dep.is_insecure()).count();
     let count_outdated = deps.iter().filter(|&(_, dep)| dep.is_outdated()).count();
+    let any_locked = deps.iter().any(|(_, dep)| dep.locked.is_some());
 
     html! {
         h3 class="title is-4" {(title)}
@@ -56,6 +215,9 @@ fn dependency_table(title: &str, deps: IndexMap<CrateName, AnalyzedDependency>)
                 tr {
                     th {"Crate"}
                     th class="has-text-right" {"Required"}
+                    @if any_locked {
+                        th class="has-text-right" {"Locked"}
+                    }
                     th class="has-text-right" {"Latest"}
                     th class="has-text-right" {"Status"}
                 }
@@ -64,23 +226,58 @@ fn dependency_table(title: &str, deps: IndexMap<CrateName, AnalyzedDependency>)
                 @for (name, dep) in deps {
                     tr {
                         td {
-                            a href=(format!("https://crates.io/crates/{}", name.as_ref())) {(name.as_ref())}
+                            a href=(format!("https://crates.io/crates/{}", dep.package_name(&name).as_ref())) {(name.as_ref())}
+                            @if let Some(ref package) = dep.package {
+                                span class="has-text-grey is-size-7" {(format!(" (package {})", package.as_ref()))}
+                            }
                         }
                         td class="has-text-right" {code {(dep.required.to_string())}}
+                        @if any_locked {
+                            td class="has-text-right" {
+                                @if let Some(ref locked) = dep.locked {
+                                    code {(locked.to_string())}
+                                } @else {
+                                    "N/A"
+                                }
+                            }
+                        }
                         td class="has-text-right" {
                             @if let Some(ref latest) = dep.latest {
                                 code {(latest.to_string())}
+                                (lag_details(dep.lag.as_ref(), dep.libyears))
                             } @else {
                                 "N/A"
                             }
                         }
                         td class="has-text-right" {
-                            @if dep.insecure {
-                                span class="tag is-danger" {"insecure"}
-                            } @else if dep.is_outdated() {
-                                span class="tag is-warning" {"out of date"}
+                            @if let Some(ref reason) = dep.ignored {
+                                span class="tag is-light" title=(reason) {"ignored"}
                             } @else {
-                                span class="tag is-success" {"up to date"}
+                                @if let Some(kind) = dep.advisory_kind() {
+                                    (advisory_tag(kind))
+                                }
+                            }
+                            @if dep.ignored.is_none() && !dep.is_insecure() {
+                                @if dep.is_unsatisfiable() {
+                                    span class="tag is-danger" title="No release matches the requirement" {"unsatisfiable"}
+                                } @else if dep.is_yanked_only() {
+                                    span class="tag is-warning" title="Only yanked releases match the requirement" {"yanked"}
+                                } @else if dep.is_outdated() {
+                                    span class="tag is-warning" {"out of date"}
+                                } @else if dep.is_behind_latest() {
+                                    span class="tag is-light" title="allowed by policy" {"compatible update"}
+                                } @else if dep.is_locked_outdated() {
+                                    span class="tag is-info" {"lock out of date"}
+                                } @else {
+                                    span class="tag is-success" {"up to date"}
+                                }
+                            }
+                        }
+                    }
+                    @if !dep.advisories.is_empty() {
+                        tr {
+                            td colspan=(if any_locked { 5 } else { 4 }) {
+                                (advisory_details(&dep))
                             }
                         }
                     }
@@ -90,6 +287,52 @@ fn dependency_table(title: &str, deps: IndexMap<CrateName, AnalyzedDependency>)
     }
 }
 
+fn policy_notification(policy: &Policy) -> Markup {
+    html! {
+        div class="notification" {
+            h2 class="title is-5" {"Policy"}
+            p {
+                "This repository acknowledges the following items in "
+                code {".deps.toml"}
+                " or "
+                code {"[package.metadata.deps-rs]"}
+                "."
+            }
+            ul {
+                @if policy.allow_minor_outdated {
+                    li {"Dependencies that only trail by semver-compatible releases are considered up to date."}
+                }
+                @for (id, reason) in policy.ignored_advisories.iter() {
+                    li {
+                        "Advisory "
+                        code {(id)}
+                        " is ignored: " (reason)
+                    }
+                }
+                @for (name, reason) in policy.ignored_crates.iter() {
+                    li {
+                        "Crate "
+                        code {(name.as_ref())}
+                        " is ignored: " (reason)
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn policy_error_notification(error: &str) -> Markup {
+    html! {
+        div class="notification is-warning" {
+            h2 class="title is-5" {"Policy"}
+            p {
+                "The policy of this repository could not be parsed, so none of its items are acknowledged."
+            }
+            pre class="is-size-7" {(error)}
+        }
+    }
+}
+
 fn get_site_icon(site: &RepoSite) -> &'static str {
     match *site {
         RepoSite::Github => "fa-github",
@@ -98,15 +341,33 @@ fn get_site_icon(site: &RepoSite) -> &'static str {
     }
 }
 
+fn repo_self_path(repo_path: &RepoPath) -> String {
+    let base_path = format!("repo/{}/{}/{}", repo_path.site.as_ref(), repo_path.qual.as_ref(), repo_path.name.as_ref());
+    match repo_path.git_ref {
+        Some(ref git_ref) =>
+            format!("{}/tree/{}", base_path, utf8_percent_encode(git_ref.as_ref(), PATH_SEGMENT_ENCODE_SET)),
+        None =>
+            base_path
+    }
+}
+
 fn render_title(subject_path: &SubjectPath) -> Markup {
     match *subject_path {
         SubjectPath::Repo(ref repo_path) => {
             let site_icon = get_site_icon(&repo_path.site);
+            let repo_uri = format!("{}/{}/{}", repo_path.site.to_base_uri(), repo_path.qual.as_ref(), repo_path.name.as_ref());
             html! {
-                a href=(format!("{}/{}/{}", repo_path.site.to_base_uri(), repo_path.qual.as_ref(), repo_path.name.as_ref())) {
+                a href=(repo_uri) {
                     i class=(format!("fa {}", site_icon)) {""}
                     (format!(" {} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref()))
                 }
+                @if let Some(ref git_ref) = repo_path.git_ref {
+                    " "
+                    a href=(format!("{}/{}/{}", repo_uri, repo_path.site.to_tree_segment(), git_ref.as_ref())) {
+                        i class="fa fa-code-fork" {""}
+                        (format!(" {}", git_ref.as_ref()))
+                    }
+                }
             }
         },
         SubjectPath::Crate(ref crate_path) => {
@@ -120,7 +381,12 @@ fn render_title(subject_path: &SubjectPath) -> Markup {
     }
 }
 
-fn render_failure(subject_path: SubjectPath) -> Markup {
+fn render_failure(kind: UpstreamErrorKind, subject_path: SubjectPath) -> Markup {
+    let subject = match subject_path {
+        SubjectPath::Repo(_) => "repository",
+        SubjectPath::Crate(_) => "crate"
+    };
+
     html! {
         section class="hero is-light" {
             div class="hero-head" {(super::render_navbar())}
@@ -135,8 +401,20 @@ fn render_failure(subject_path: SubjectPath) -> Markup {
         section class="section" {
             div class="container" {
                 div class="notification is-danger" {
-                    h2 class="title is-3" {"Failed to analyze repository"}
-                    p {"The repository you requested might be structured in an uncommon way that is not yet supported."}
+                    @match kind {
+                        UpstreamErrorKind::NotFound => {
+                            h2 class="title is-3" {(format!("Could not find {}", subject))}
+                            p {(format!("The {} you requested does not exist, or is not publicly accessible.", subject))}
+                        },
+                        UpstreamErrorKind::RateLimited => {
+                            h2 class="title is-3" {(format!("Failed to analyze {}", subject))}
+                            p {"We are currently being rate limited by an upstream service. Please try again later."}
+                        },
+                        UpstreamErrorKind::Transient => {
+                            h2 class="title is-3" {(format!("Failed to analyze {}", subject))}
+                            p {(format!("The {} you requested might be structured in an uncommon way that is not yet supported.", subject))}
+                        }
+                    }
                 }
             }
         }
@@ -147,20 +425,20 @@ fn render_failure(subject_path: SubjectPath) -> Markup {
 fn render_success(analysis_outcome: AnalyzeDependenciesOutcome, subject_path: SubjectPath) -> Markup {
     let self_path = match subject_path {
         SubjectPath::Repo(ref repo_path) =>
-            format!("repo/{}/{}/{}", repo_path.site.as_ref(), repo_path.qual.as_ref(), repo_path.name.as_ref()),
+            repo_self_path(repo_path),
         SubjectPath::Crate(ref crate_path) =>
             format!("crate/{}/{}", crate_path.name.as_ref(), crate_path.version)
     };
     let status_base_url = format!("{}/{}", &super::SELF_BASE_URL as &str, self_path);
 
-    let status_data_uri = badge::badge(Some(&analysis_outcome)).to_svg_data_uri();
+    let status_data_uri = badge::badge(Ok(&analysis_outcome), &badge::BadgeCustomization::default()).to_svg_data_uri();
 
-    let hero_class = if analysis_outcome.any_insecure()  {
-        "is-danger"
-    } else if analysis_outcome.any_outdated() {
-        "is-warning"
-    } else {
-        "is-success"
+    let hero_class = match analysis_outcome.advisory_kind() {
+        Some(AdvisoryKind::Vulnerability) => "is-danger",
+        _ if analysis_outcome.any_unsatisfiable() => "is-danger",
+        Some(AdvisoryKind::Unsound) | Some(AdvisoryKind::Unmaintained) => "is-warning",
+        _ if analysis_outcome.any_yanked_only() || analysis_outcome.any_outdated() => "is-warning",
+        _ => "is-success"
     };
 
     html! {
@@ -185,6 +463,12 @@ fn render_success(analysis_outcome: AnalyzeDependenciesOutcome, subject_path: Su
         }
         section class="section" {
             div class="container" {
+                @if let Some(ref policy_error) = analysis_outcome.policy_error {
+                    (policy_error_notification(policy_error))
+                }
+                @if !analysis_outcome.policy.is_empty() {
+                    (policy_notification(&analysis_outcome.policy))
+                }
                 @for (crate_name, deps) in analysis_outcome.crates {
                     (dependency_tables(crate_name, deps))
                 }
@@ -194,17 +478,20 @@ fn render_success(analysis_outcome: AnalyzeDependenciesOutcome, subject_path: Su
     }
 }
 
-pub fn render(analysis_outcome: Option<AnalyzeDependenciesOutcome>, subject_path: SubjectPath) -> Response {
+pub fn render(analysis_result: Result<AnalyzeDependenciesOutcome, UpstreamErrorKind>, subject_path: SubjectPath) -> Response {
     let title = match subject_path {
-        SubjectPath::Repo(ref repo_path) =>
-            format!("{} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref()),
+        SubjectPath::Repo(ref repo_path) => match repo_path.git_ref {
+            Some(ref git_ref) =>
+                format!("{} / {} @ {}", repo_path.qual.as_ref(), repo_path.name.as_ref(), git_ref.as_ref()),
+            None =>
+                format!("{} / {}", repo_path.qual.as_ref(), repo_path.name.as_ref())
+        },
         SubjectPath::Crate(ref crate_path) =>
             format!("{} {}", crate_path.name.as_ref(), crate_path.version)
     };
 
-    if let Some(outcome) = analysis_outcome {
-        super::render_html(&title, render_success(outcome, subject_path))
-    } else {
-        super::render_html(&title, render_failure(subject_path))
+    match analysis_result {
+        Ok(outcome) => super::render_html(&title, render_success(outcome, subject_path)),
+        Err(kind) => super::render_html(&title, render_failure(kind, subject_path))
     }
 }
@@ -0,0 +1,345 @@
+use hyper::Response;
+use hyper::header::ContentType;
+use indexmap::IndexMap;
+use serde_json;
+
+use ::engine::AnalyzeDependenciesOutcome;
+use ::interactors::UpstreamErrorKind;
+use ::models::crates::{Advisory, CrateName, AnalyzedDependency, AnalyzedDependencies, Lag, TransitiveDependency};
+use ::models::SubjectPath;
+
+/// Version of the JSON schema, to be bumped on any incompatible change
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct StatusJson {
+    schema_version: u32,
+    subject: SubjectJson,
+    analysis: Option<AnalysisJson>,
+    error: Option<&'static str>
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum SubjectJson {
+    Repo {
+        site: String,
+        qual: String,
+        name: String,
+        #[serde(rename = "ref")]
+        git_ref: Option<String>
+    },
+    Crate { name: String, version: String }
+}
+
+#[derive(Serialize)]
+struct AnalysisJson {
+    duration_ms: u64,
+    outdated: bool,
+    insecure: bool,
+    crates: Vec<CrateJson>
+}
+
+#[derive(Serialize)]
+struct CrateJson {
+    name: String,
+    libyears: f64,
+    dependencies: Vec<DependencyJson>,
+    transitive: Vec<TransitiveJson>
+}
+
+#[derive(Serialize)]
+struct DependencyJson {
+    name: String,
+    package: String,
+    kind: &'static str,
+    target: Option<String>,
+    required: String,
+    locked: Option<String>,
+    locked_outdated: bool,
+    latest_that_matches: Option<String>,
+    latest: Option<String>,
+    outdated: bool,
+    yanked_only: bool,
+    unsatisfiable: bool,
+    lag: Option<LagJson>,
+    libyears: Option<f64>,
+    insecure: bool,
+    advisories: Vec<AdvisoryJson>,
+    safe_version: Option<String>,
+    ignored: Option<String>
+}
+
+#[derive(Serialize)]
+struct LagJson {
+    kind: &'static str,
+    releases: usize
+}
+
+#[derive(Serialize)]
+struct AdvisoryJson {
+    id: String,
+    kind: &'static str,
+    cvss: Option<CvssJson>,
+    title: String,
+    url: Option<String>,
+    patched_versions: Vec<String>,
+    ignored: Option<String>
+}
+
+#[derive(Serialize)]
+struct CvssJson {
+    vector: String,
+    base_score: f64
+}
+
+#[derive(Serialize)]
+struct TransitiveJson {
+    name: String,
+    version: String,
+    path: Vec<String>,
+    insecure: bool,
+    advisory_kind: Option<&'static str>,
+    yanked: bool
+}
+
+fn convert_subject(subject_path: &SubjectPath) -> SubjectJson {
+    match *subject_path {
+        SubjectPath::Repo(ref repo_path) => SubjectJson::Repo {
+            site: repo_path.site.as_ref().to_string(),
+            qual: repo_path.qual.as_ref().to_string(),
+            name: repo_path.name.as_ref().to_string(),
+            git_ref: repo_path.git_ref.as_ref().map(|git_ref| git_ref.as_ref().to_string())
+        },
+        SubjectPath::Crate(ref crate_path) => SubjectJson::Crate {
+            name: crate_path.name.as_ref().to_string(),
+            version: crate_path.version.to_string()
+        }
+    }
+}
+
+fn convert_dependencies(kind: &'static str, target: Option<&str>, deps: &IndexMap<CrateName, AnalyzedDependency>) -> Vec<DependencyJson> {
+    deps.iter().map(|(name, dep)| {
+        DependencyJson {
+            name: name.as_ref().to_string(),
+            package: dep.package_name(name).as_ref().to_string(),
+            kind,
+            target: target.map(|target| target.to_string()),
+            required: dep.required.to_string(),
+            locked: dep.locked.as_ref().map(|v| v.to_string()),
+            locked_outdated: dep.is_locked_outdated(),
+            latest_that_matches: dep.latest_that_matches.as_ref().map(|v| v.to_string()),
+            latest: dep.latest.as_ref().map(|v| v.to_string()),
+            outdated: dep.is_outdated(),
+            yanked_only: dep.is_yanked_only(),
+            unsatisfiable: dep.is_unsatisfiable(),
+            lag: dep.lag.as_ref().map(convert_lag),
+            libyears: dep.libyears,
+            insecure: dep.is_insecure(),
+            advisories: convert_advisories(&dep.advisories),
+            safe_version: dep.safe_version.as_ref().map(|v| v.to_string()),
+            ignored: dep.ignored.clone()
+        }
+    }).collect()
+}
+
+fn convert_lag(lag: &Lag) -> LagJson {
+    LagJson {
+        kind: lag.kind.as_str(),
+        releases: lag.releases
+    }
+}
+
+fn convert_advisories(advisories: &[Advisory]) -> Vec<AdvisoryJson> {
+    advisories.iter().map(|advisory| {
+        AdvisoryJson {
+            id: advisory.id.clone(),
+            kind: advisory.kind.as_str(),
+            cvss: advisory.cvss.as_ref().map(|cvss| CvssJson {
+                vector: cvss.vector.clone(),
+                base_score: cvss.base_score
+            }),
+            title: advisory.title.clone(),
+            url: advisory.url.clone(),
+            patched_versions: advisory.patched_versions.iter().map(|req| req.to_string()).collect(),
+            ignored: advisory.ignored.clone()
+        }
+    }).collect()
+}
+
+fn convert_transitive(deps: &[TransitiveDependency]) -> Vec<TransitiveJson> {
+    deps.iter().map(|dep| {
+        TransitiveJson {
+            name: dep.name.as_ref().to_string(),
+            version: dep.version.to_string(),
+            path: dep.path.iter().map(|name| name.as_ref().to_string()).collect(),
+            insecure: dep.is_insecure(),
+            advisory_kind: dep.advisory_kind.map(|kind| kind.as_str()),
+            yanked: dep.yanked
+        }
+    }).collect()
+}
+
+fn convert_all_dependencies(target: Option<&str>, deps: &AnalyzedDependencies) -> Vec<DependencyJson> {
+    let mut dependencies = convert_dependencies("normal", target, &deps.main);
+    dependencies.extend(convert_dependencies("dev", target, &deps.dev));
+    dependencies.extend(convert_dependencies("build", target, &deps.build));
+
+    for (target, target_deps) in deps.targets.iter() {
+        dependencies.extend(convert_all_dependencies(Some(target), target_deps));
+    }
+
+    dependencies
+}
+
+fn convert_analysis(analysis_outcome: &AnalyzeDependenciesOutcome) -> AnalysisJson {
+    let duration = analysis_outcome.duration;
+    let duration_ms = duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000 / 1000) as u64;
+
+    let crates = analysis_outcome.crates.iter().map(|&(ref crate_name, ref deps)| {
+        CrateJson {
+            name: crate_name.as_ref().to_string(),
+            libyears: deps.libyears(),
+            dependencies: convert_all_dependencies(None, deps),
+            transitive: convert_transitive(&deps.transitive)
+        }
+    }).collect();
+
+    AnalysisJson {
+        duration_ms,
+        outdated: analysis_outcome.any_outdated(),
+        insecure: analysis_outcome.any_insecure(),
+        crates
+    }
+}
+
+fn convert_error(kind: UpstreamErrorKind) -> &'static str {
+    match kind {
+        UpstreamErrorKind::NotFound => "not_found",
+        UpstreamErrorKind::RateLimited => "rate_limited",
+        UpstreamErrorKind::Transient => "transient"
+    }
+}
+
+pub fn response(analysis_result: Result<&AnalyzeDependenciesOutcome, UpstreamErrorKind>, subject_path: &SubjectPath) -> Response {
+    let status = StatusJson {
+        schema_version: SCHEMA_VERSION,
+        subject: convert_subject(subject_path),
+        analysis: analysis_result.ok().map(convert_analysis),
+        error: analysis_result.err().map(convert_error)
+    };
+
+    let body = serde_json::to_vec(&status)
+        .expect("failed to serialize status");
+
+    Response::new()
+        .with_header(ContentType::json())
+        .with_body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::{Future, Stream};
+    use serde_json::{self, Value};
+
+    use engine::AnalyzeDependenciesOutcome;
+    use interactors::UpstreamErrorKind;
+    use models::SubjectPath;
+    use models::crates::{AnalyzedDependency, AnalyzedDependencies, CrateDeps, CratePath, Lag, LagKind, TransitiveDependency};
+    use models::policy::Policy;
+    use super::response;
+
+    #[test]
+    fn serializes_schema_version_1() {
+        let mut dep = AnalyzedDependency::new("^1.0.0".parse().unwrap());
+        dep.locked = Some("1.0.0".parse().unwrap());
+        dep.latest_that_matches = Some("1.0.5".parse().unwrap());
+        dep.latest = Some("2.0.0".parse().unwrap());
+        dep.lag = Some(Lag { kind: LagKind::Major, releases: 1 });
+        dep.libyears = Some(0.5);
+
+        let mut deps = AnalyzedDependencies::new(&CrateDeps::default());
+        deps.main.insert("serde".parse().unwrap(), dep);
+        deps.transitive.push(TransitiveDependency {
+            name: "itoa".parse().unwrap(),
+            version: "0.4.1".parse().unwrap(),
+            path: vec!["serde".parse().unwrap()],
+            advisory_kind: None,
+            yanked: true
+        });
+
+        let outcome = AnalyzeDependenciesOutcome {
+            crates: vec![("demo".parse().unwrap(), deps)],
+            policy: Arc::new(Policy::default()),
+            policy_error: None,
+            duration: Duration::from_millis(1500)
+        };
+        let subject_path = SubjectPath::Crate(CratePath::from_parts("demo", "0.1.0").unwrap());
+
+        let body = response(Ok(&outcome), &subject_path).body().concat2().wait().unwrap();
+        let actual: Value = serde_json::from_slice(&body).unwrap();
+        let expected: Value = serde_json::from_str(r#"{
+            "schema_version": 1,
+            "subject": { "type": "crate", "name": "demo", "version": "0.1.0" },
+            "analysis": {
+                "duration_ms": 1500,
+                "outdated": true,
+                "insecure": false,
+                "crates": [{
+                    "name": "demo",
+                    "libyears": 0.5,
+                    "dependencies": [{
+                        "name": "serde",
+                        "package": "serde",
+                        "kind": "normal",
+                        "target": null,
+                        "required": "^1.0.0",
+                        "locked": "1.0.0",
+                        "locked_outdated": true,
+                        "latest_that_matches": "1.0.5",
+                        "latest": "2.0.0",
+                        "outdated": true,
+                        "yanked_only": false,
+                        "unsatisfiable": false,
+                        "lag": { "kind": "major", "releases": 1 },
+                        "libyears": 0.5,
+                        "insecure": false,
+                        "advisories": [],
+                        "safe_version": null,
+                        "ignored": null
+                    }],
+                    "transitive": [{
+                        "name": "itoa",
+                        "version": "0.4.1",
+                        "path": ["serde"],
+                        "insecure": false,
+                        "advisory_kind": null,
+                        "yanked": true
+                    }]
+                }]
+            },
+            "error": null
+        }"#).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn serializes_errors() {
+        let subject_path = SubjectPath::Crate(CratePath::from_parts("demo", "0.1.0").unwrap());
+
+        let body = response(Err(UpstreamErrorKind::RateLimited), &subject_path).body().concat2().wait().unwrap();
+        let actual: Value = serde_json::from_slice(&body).unwrap();
+        let expected: Value = serde_json::from_str(r#"{
+            "schema_version": 1,
+            "subject": { "type": "crate", "name": "demo", "version": "0.1.0" },
+            "analysis": null,
+            "error": "rate_limited"
+        }"#).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+}
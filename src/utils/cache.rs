@@ -2,22 +2,43 @@ use std::fmt::{Debug, Formatter, Result as FmtResult};
 use std::hash::Hash;
 use std::time::{Duration, Instant};
 use std::ops::Deref;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use failure::Error;
 use futures::{Future, Poll};
 use futures::future::{FromErr, Shared, SharedItem};
 use lru_cache::LruCache;
 use shared_failure::SharedFailure;
+use tokio_core::reactor::Handle;
 use tokio_service::Service;
 
+use ::interactors::{UpstreamError, UpstreamErrorKind};
+
+/// Time to wait after a failed background refresh before trying again
+const REFRESH_RETRY_SECS: u64 = 60;
+
+struct CacheEntry<F: Future<Error=Error>> {
+    inserted_at: Instant,
+    valid_until: Instant,
+    shared_future: Shared<FromErr<F, SharedFailure>>,
+    refreshing: bool,
+    refresh_failed_at: Option<Instant>
+}
+
+struct Revalidation {
+    handle: Handle,
+    max_staleness: Duration
+}
+
 pub struct Cache<S>
     where S: Service<Error=Error>,
           S::Request: Hash + Eq
 {
     inner: S,
     duration: Duration,
-    cache: Mutex<LruCache<S::Request, (Instant, Shared<FromErr<S::Future, SharedFailure>>)>>
+    revalidation: Option<Revalidation>,
+    not_found_duration: Option<Duration>,
+    cache: Arc<Mutex<LruCache<S::Request, CacheEntry<S::Future>>>>
 }
 
 impl<S> Debug for Cache<S>
@@ -28,6 +49,8 @@ impl<S> Debug for Cache<S>
         fmt.debug_struct("Cache")
             .field("inner", &self.inner)
             .field("duration", &self.duration)
+            .field("max_staleness", &self.revalidation.as_ref().map(|revalidation| revalidation.max_staleness))
+            .field("not_found_duration", &self.not_found_duration)
             .finish()
     }
 }
@@ -40,14 +63,70 @@ impl<S> Cache<S>
         Cache {
             inner: service,
             duration: duration,
-            cache: Mutex::new(LruCache::new(capacity))
+            revalidation: None,
+            not_found_duration: None,
+            cache: Arc::new(Mutex::new(LruCache::new(capacity)))
         }
     }
+
+    /// Serve expired entries for up to `max_staleness` past their expiry,
+    /// while refreshing them in the background on the given event loop.
+    ///
+    /// If a refresh fails, the stale entry keeps being served until it
+    /// exceeds `max_staleness`, and the refresh is retried at most once a minute.
+    pub fn revalidate_in_background(mut self, handle: Handle, max_staleness: Duration) -> Cache<S> {
+        self.revalidation = Some(Revalidation { handle, max_staleness });
+        self
+    }
+
+    /// Remember requests that failed because the upstream resource does
+    /// not exist for `duration`, instead of retrying them on every call.
+    pub fn cache_not_found(mut self, duration: Duration) -> Cache<S> {
+        self.not_found_duration = Some(duration);
+        self
+    }
+}
+
+impl<S> Cache<S>
+    where S: Service<Error=Error>,
+          S::Request: Clone + Hash + Eq + 'static,
+          S::Response: 'static,
+          S::Future: 'static
+{
+    fn spawn_refresh(&self, handle: &Handle, req: S::Request, inserted_at: Instant) {
+        let shared_future = self.inner.call(req.clone()).from_err().shared();
+        let cache = self.cache.clone();
+        let duration = self.duration;
+
+        handle.spawn(shared_future.clone().then(move |result| {
+            let mut cache = cache.lock().expect("lock poisoned");
+            if let Some(entry) = cache.get_mut(&req) {
+                // the entry may have been replaced by a synchronous fetch in the meantime
+                if entry.inserted_at != inserted_at {
+                    return Ok(());
+                }
+                let now = Instant::now();
+                entry.refreshing = false;
+                // on failure, keep serving the stale value
+                if result.is_ok() {
+                    entry.inserted_at = now;
+                    entry.valid_until = now + duration;
+                    entry.shared_future = shared_future;
+                    entry.refresh_failed_at = None;
+                } else {
+                    entry.refresh_failed_at = Some(now);
+                }
+            }
+            Ok(())
+        }));
+    }
 }
 
 impl<S> Service for Cache<S>
     where S: Service<Error=Error>,
-          S::Request: Clone + Hash + Eq
+          S::Request: Clone + Hash + Eq + 'static,
+          S::Response: 'static,
+          S::Future: 'static
 {
     type Request = S::Request;
     type Response = CachedItem<S::Response>;
@@ -57,15 +136,44 @@ impl<S> Service for Cache<S>
     fn call(&self, req: Self::Request) -> Self::Future {
         let now = Instant::now();
         let mut cache = self.cache.lock().expect("lock poisoned");
-        if let Some(&mut (valid_until, ref shared_future)) = cache.get_mut(&req) {
-            if valid_until > now {
-                if let Some(Ok(_)) = shared_future.peek() {
-                    return Cached(shared_future.clone());
-                }
+        if let Some(entry) = cache.get_mut(&req) {
+            match entry.shared_future.peek() {
+                Some(Ok(_)) => {
+                    if entry.valid_until > now {
+                        return Cached(entry.shared_future.clone());
+                    }
+                    if let Some(ref revalidation) = self.revalidation {
+                        if entry.valid_until + revalidation.max_staleness > now {
+                            let may_retry = entry.refresh_failed_at
+                                .map(|failed_at| failed_at + Duration::from_secs(REFRESH_RETRY_SECS) <= now)
+                                .unwrap_or(true);
+                            if !entry.refreshing && may_retry {
+                                entry.refreshing = true;
+                                self.spawn_refresh(&revalidation.handle, req.clone(), entry.inserted_at);
+                            }
+                            return Cached(entry.shared_future.clone());
+                        }
+                    }
+                },
+                Some(Err(err)) => {
+                    if let Some(not_found_duration) = self.not_found_duration {
+                        let is_not_found = UpstreamError::classify_shared(&err) == UpstreamErrorKind::NotFound;
+                        if is_not_found && entry.inserted_at + not_found_duration > now {
+                            return Cached(entry.shared_future.clone());
+                        }
+                    }
+                },
+                None => {}
             }
         }
         let shared_future = self.inner.call(req.clone()).from_err().shared();
-        cache.insert(req, (now + self.duration, shared_future.clone()));
+        cache.insert(req, CacheEntry {
+            inserted_at: now,
+            valid_until: now + self.duration,
+            shared_future: shared_future.clone(),
+            refreshing: false,
+            refresh_failed_at: None
+        });
         Cached(shared_future)
     }
 }
@@ -102,3 +210,174 @@ impl<T> Deref for CachedItem<T> {
         &self.0.deref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use failure::Error;
+    use futures::{Future, future};
+    use futures::future::FutureResult;
+    use tokio_core::reactor::Core;
+    use tokio_service::Service;
+
+    use interactors::{UpstreamError, UpstreamErrorKind};
+    use super::Cache;
+
+    /// Answers with the number of calls made so far, unless set up to fail
+    #[derive(Clone)]
+    struct StubService {
+        calls: Arc<Mutex<u32>>,
+        failure: Arc<Mutex<Option<UpstreamErrorKind>>>
+    }
+
+    impl StubService {
+        fn new() -> StubService {
+            StubService {
+                calls: Arc::new(Mutex::new(0)),
+                failure: Arc::new(Mutex::new(None))
+            }
+        }
+
+        fn calls(&self) -> u32 {
+            *self.calls.lock().unwrap()
+        }
+
+        fn fail_with(&self, failure: Option<UpstreamErrorKind>) {
+            *self.failure.lock().unwrap() = failure;
+        }
+    }
+
+    impl Service for StubService {
+        type Request = &'static str;
+        type Response = u32;
+        type Error = Error;
+        type Future = FutureResult<u32, Error>;
+
+        fn call(&self, _req: &'static str) -> Self::Future {
+            let mut calls = self.calls.lock().unwrap();
+            *calls += 1;
+            match *self.failure.lock().unwrap() {
+                Some(kind) => future::err(UpstreamError::new(kind, "stub failure".to_string()).into()),
+                None => future::ok(*calls)
+            }
+        }
+    }
+
+    fn call_kind<S>(cache: &Cache<S>) -> Result<u32, UpstreamErrorKind>
+        where S: Service<Request=&'static str, Response=u32, Error=Error>, S::Future: 'static
+    {
+        cache.call("key").wait()
+            .map(|value| *value)
+            .map_err(|err| UpstreamError::classify_shared(&err))
+    }
+
+    /// Lets background refreshes spawned on the core write back their results
+    fn run_refreshes(core: &mut Core) {
+        core.turn(Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn caches_not_found_failures() {
+        let service = StubService::new();
+        service.fail_with(Some(UpstreamErrorKind::NotFound));
+        let cache = Cache::new(service.clone(), Duration::from_secs(60), 10)
+            .cache_not_found(Duration::from_secs(60));
+
+        assert_eq!(call_kind(&cache), Err(UpstreamErrorKind::NotFound));
+        assert_eq!(call_kind(&cache), Err(UpstreamErrorKind::NotFound));
+        assert_eq!(service.calls(), 1);
+    }
+
+    #[test]
+    fn retries_not_found_failures_by_default() {
+        let service = StubService::new();
+        service.fail_with(Some(UpstreamErrorKind::NotFound));
+        let cache = Cache::new(service.clone(), Duration::from_secs(60), 10);
+
+        assert_eq!(call_kind(&cache), Err(UpstreamErrorKind::NotFound));
+        assert_eq!(call_kind(&cache), Err(UpstreamErrorKind::NotFound));
+        assert_eq!(service.calls(), 2);
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let service = StubService::new();
+        service.fail_with(Some(UpstreamErrorKind::Transient));
+        let cache = Cache::new(service.clone(), Duration::from_secs(60), 10)
+            .cache_not_found(Duration::from_secs(60));
+
+        assert_eq!(call_kind(&cache), Err(UpstreamErrorKind::Transient));
+        service.fail_with(None);
+        assert_eq!(call_kind(&cache), Ok(2));
+        assert_eq!(call_kind(&cache), Ok(2));
+        assert_eq!(service.calls(), 2);
+    }
+
+    #[test]
+    fn serves_stale_entries_while_refreshing_once() {
+        let mut core = Core::new().unwrap();
+        let service = StubService::new();
+        let cache = Cache::new(service.clone(), Duration::from_secs(0), 10)
+            .revalidate_in_background(core.handle(), Duration::from_secs(3600));
+
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(service.calls(), 2);
+
+        run_refreshes(&mut core);
+        assert_eq!(call_kind(&cache), Ok(2));
+        assert_eq!(service.calls(), 3);
+    }
+
+    #[test]
+    fn backs_off_after_failed_refresh() {
+        let mut core = Core::new().unwrap();
+        let service = StubService::new();
+        let cache = Cache::new(service.clone(), Duration::from_secs(0), 10)
+            .revalidate_in_background(core.handle(), Duration::from_secs(3600));
+
+        assert_eq!(call_kind(&cache), Ok(1));
+        service.fail_with(Some(UpstreamErrorKind::Transient));
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(service.calls(), 2);
+
+        run_refreshes(&mut core);
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(service.calls(), 2);
+    }
+
+    #[test]
+    fn fetches_entries_past_max_staleness() {
+        let core = Core::new().unwrap();
+        let service = StubService::new();
+        let cache = Cache::new(service.clone(), Duration::from_secs(0), 10)
+            .revalidate_in_background(core.handle(), Duration::from_secs(0));
+
+        assert_eq!(call_kind(&cache), Ok(1));
+        assert_eq!(call_kind(&cache), Ok(2));
+        assert_eq!(service.calls(), 2);
+    }
+
+    #[test]
+    fn ignores_refreshes_of_replaced_entries() {
+        let mut core = Core::new().unwrap();
+        let service = StubService::new();
+        let cache = Cache::new(service.clone(), Duration::from_secs(0), 10)
+            .revalidate_in_background(core.handle(), Duration::from_millis(50));
+
+        assert_eq!(call_kind(&cache), Ok(1));
+        // starts a refresh that only completes once the core runs
+        assert_eq!(call_kind(&cache), Ok(1));
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(call_kind(&cache), Ok(3));
+
+        run_refreshes(&mut core);
+        assert_eq!(call_kind(&cache), Ok(3));
+    }
+}
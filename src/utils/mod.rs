@@ -1 +1,3 @@
 pub mod cache;
+pub mod store;
+pub mod throttle;
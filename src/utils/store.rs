@@ -0,0 +1,383 @@
+use std::fmt::Debug;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use futures::{Future, future};
+use futures_cpupool::CpuPool;
+use relative_path::RelativePathBuf;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use slog::Logger;
+use tokio_service::Service;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+
+use ::interactors::{UpstreamError, UpstreamErrorKind};
+use ::models::crates::{CrateName, CratePath};
+use ::models::repo::RepoPath;
+
+/// Backend for cache entries that need to survive a restart
+pub trait Store: Debug + Send + Sync {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    fn save(&self, key: &str, value: &[u8]) -> Result<(), Error>;
+}
+
+/// Stores every entry as a separate file inside of a directory
+#[derive(Debug)]
+pub struct FileStore {
+    root: PathBuf
+}
+
+impl FileStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> FileStore {
+        FileStore { root: root.into() }
+    }
+
+    fn path_for_key(&self, key: &str) -> PathBuf {
+        self.root.join(utf8_percent_encode(key, PATH_SEGMENT_ENCODE_SET).to_string())
+    }
+}
+
+impl Store for FileStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(self.path_for_key(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into())
+        }
+    }
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        fs::create_dir_all(&self.root)?;
+
+        let path = self.path_for_key(key);
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        // write to a temporary file first, so that readers never see a partial entry
+        fs::write(&tmp_path, value)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+}
+
+/// Requests that can be used to address an entry in a `Store`
+pub trait StoreKey {
+    fn store_key(&self) -> String;
+}
+
+impl StoreKey for () {
+    fn store_key(&self) -> String {
+        String::new()
+    }
+}
+
+impl StoreKey for CrateName {
+    fn store_key(&self) -> String {
+        self.as_ref().to_string()
+    }
+}
+
+impl StoreKey for CratePath {
+    fn store_key(&self) -> String {
+        format!("{}/{}", self.name.as_ref(), self.version)
+    }
+}
+
+impl StoreKey for (RepoPath, RelativePathBuf) {
+    fn store_key(&self) -> String {
+        let (ref repo_path, ref path) = *self;
+        format!("{}/{}/{}/{}/{}", repo_path.site.as_ref(), repo_path.qual.as_ref(),
+            repo_path.name.as_ref(), repo_path.revision(), path.as_str())
+    }
+}
+
+#[derive(Serialize)]
+struct StoredEntryRef<'a, T: 'a> {
+    expires_at: u64,
+    value: &'a T
+}
+
+#[derive(Deserialize)]
+struct StoredEntry<T> {
+    expires_at: u64,
+    value: T
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Persists responses of the inner service in a `Store`, along with their expiry.
+/// The store is accessed on a thread pool, to keep file I/O off the event loop.
+///
+/// Failing to read or write the store is never fatal, the inner service is
+/// called instead and the response just isn't persisted. Expired entries are
+/// kept, and served if the inner service fails for any reason but the
+/// resource not existing upstream.
+#[derive(Debug)]
+pub struct Persisted<S> {
+    inner: Arc<S>,
+    store: Option<Arc<Store>>,
+    pool: CpuPool,
+    logger: Logger,
+    namespace: &'static str,
+    duration: Duration
+}
+
+impl<S> Persisted<S> {
+    pub fn new(service: S, store: Option<Arc<Store>>, pool: CpuPool, logger: Logger, namespace: &'static str, duration: Duration) -> Persisted<S> {
+        Persisted {
+            inner: Arc::new(service),
+            store, pool, logger, namespace, duration
+        }
+    }
+}
+
+impl<S> Service for Persisted<S>
+    where S: Service<Error=Error> + 'static,
+          S::Request: StoreKey + 'static,
+          S::Response: Serialize + DeserializeOwned + Send + 'static,
+          S::Future: 'static
+{
+    type Request = S::Request;
+    type Response = S::Response;
+    type Error = Error;
+    type Future = Box<Future<Item=Self::Response, Error=Self::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let store = match self.store {
+            Some(ref store) => store.clone(),
+            None => return Box::new(self.inner.call(req))
+        };
+
+        let key = format!("{}/{}", self.namespace, req.store_key());
+        let now = unix_now();
+        let expires_at = now + self.duration.as_secs();
+
+        let load_future = {
+            let store = store.clone();
+            let key = key.clone();
+            self.pool.spawn_fn(move || {
+                let entry = store.load(&key).ok()
+                    .and_then(|bytes| bytes)
+                    .and_then(|bytes| serde_json::from_slice::<StoredEntry<S::Response>>(&bytes).ok());
+                Ok(entry) as Result<_, Error>
+            })
+        };
+
+        let inner = self.inner.clone();
+        let pool = self.pool.clone();
+        let logger = self.logger.clone();
+
+        Box::new(load_future.and_then(move |entry: Option<StoredEntry<S::Response>>| {
+            let stale = match entry {
+                Some(entry) => {
+                    if entry.expires_at > now {
+                        return future::Either::A(future::ok(entry.value));
+                    }
+                    Some(entry.value)
+                },
+                None => None
+            };
+
+            future::Either::B(inner.call(req).then(move |result| match result {
+                Ok(value) => {
+                    {
+                        let entry = StoredEntryRef { expires_at, value: &value };
+                        if let Ok(bytes) = serde_json::to_vec(&entry) {
+                            pool.spawn_fn(move || {
+                                if let Err(err) = store.save(&key, &bytes) {
+                                    warn!(logger, "failed to persist {}: {}", key, err);
+                                }
+                                Ok(()) as Result<(), ()>
+                            }).forget();
+                        }
+                    }
+                    Ok(value)
+                },
+                Err(err) => match stale {
+                    Some(value) if UpstreamError::classify(&err) != UpstreamErrorKind::NotFound => Ok(value),
+                    _ => Err(err)
+                }
+            }))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use failure::Error;
+    use futures::{Future, future};
+    use futures::future::FutureResult;
+    use futures_cpupool::CpuPool;
+    use serde_json;
+    use slog::{Discard, Logger};
+    use tokio_service::Service;
+
+    use interactors::{UpstreamError, UpstreamErrorKind};
+    use models::crates::CrateName;
+    use super::{FileStore, Persisted, Store, StoredEntryRef, unix_now};
+
+    /// A store directory that is removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = env::temp_dir().join(format!("deps-rs-store-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Answers with the number of calls made so far, unless set up to fail
+    #[derive(Clone, Debug)]
+    struct StubService {
+        calls: Arc<Mutex<u32>>,
+        failure: Option<UpstreamErrorKind>
+    }
+
+    impl StubService {
+        fn new(failure: Option<UpstreamErrorKind>) -> StubService {
+            StubService { calls: Arc::new(Mutex::new(0)), failure }
+        }
+
+        fn calls(&self) -> u32 {
+            *self.calls.lock().unwrap()
+        }
+    }
+
+    impl Service for StubService {
+        type Request = CrateName;
+        type Response = u32;
+        type Error = Error;
+        type Future = FutureResult<u32, Error>;
+
+        fn call(&self, _req: CrateName) -> Self::Future {
+            let mut calls = self.calls.lock().unwrap();
+            *calls += 1;
+            match self.failure {
+                Some(kind) => future::err(UpstreamError::new(kind, "stub failure".to_string()).into()),
+                None => future::ok(*calls + 100)
+            }
+        }
+    }
+
+    fn persisted(service: StubService, store: Arc<FileStore>) -> Persisted<StubService> {
+        let logger = Logger::root(Discard, o!());
+        Persisted::new(service, Some(store as Arc<Store>), CpuPool::new(1), logger, "test", Duration::from_secs(60))
+    }
+
+    fn seed(store: &FileStore, expires_at: u64, value: u32) {
+        let bytes = serde_json::to_vec(&StoredEntryRef { expires_at, value: &value }).unwrap();
+        store.save("test/serde", &bytes).unwrap();
+    }
+
+    fn load(store: &FileStore) -> Option<u32> {
+        store.load("test/serde").unwrap()
+            .map(|bytes| serde_json::from_slice::<super::StoredEntry<u32>>(&bytes).unwrap().value)
+    }
+
+    fn serde() -> CrateName {
+        "serde".parse().unwrap()
+    }
+
+    #[test]
+    fn encodes_keys_as_single_file_names() {
+        let dir = TempDir::new("encoding");
+        let store = FileStore::new(dir.0.clone());
+
+        assert_eq!(store.load("github/foo/bar").unwrap(), None);
+
+        store.save("github/foo/bar", b"value").unwrap();
+        assert_eq!(store.load("github/foo/bar").unwrap(), Some(b"value".to_vec()));
+        assert!(dir.0.join("github%2Ffoo%2Fbar").is_file());
+        assert!(!dir.0.join("github").exists());
+    }
+
+    #[test]
+    fn serves_fresh_entries_from_the_store() {
+        let dir = TempDir::new("fresh");
+        let store = Arc::new(FileStore::new(dir.0.clone()));
+        seed(&store, unix_now() + 60, 7);
+
+        let service = StubService::new(None);
+        let value = persisted(service.clone(), store).call(serde()).wait().unwrap();
+
+        assert_eq!(value, 7);
+        assert_eq!(service.calls(), 0);
+    }
+
+    #[test]
+    fn refetches_and_persists_expired_entries() {
+        let dir = TempDir::new("expired");
+        let store = Arc::new(FileStore::new(dir.0.clone()));
+        seed(&store, unix_now() - 1, 7);
+
+        let service = StubService::new(None);
+        let value = persisted(service.clone(), store.clone()).call(serde()).wait().unwrap();
+
+        assert_eq!(value, 101);
+        assert_eq!(service.calls(), 1);
+
+        // the entry is written in the background
+        for _ in 0..100 {
+            if load(&store) == Some(101) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("response was not persisted");
+    }
+
+    #[test]
+    fn serves_stale_entries_on_transient_failures() {
+        let dir = TempDir::new("stale");
+        let store = Arc::new(FileStore::new(dir.0.clone()));
+        seed(&store, unix_now() - 1, 7);
+
+        let service = StubService::new(Some(UpstreamErrorKind::Transient));
+        let value = persisted(service.clone(), store).call(serde()).wait().unwrap();
+
+        assert_eq!(value, 7);
+        assert_eq!(service.calls(), 1);
+    }
+
+    #[test]
+    fn does_not_serve_or_persist_not_found_failures() {
+        let dir = TempDir::new("not-found");
+        let store = Arc::new(FileStore::new(dir.0.clone()));
+
+        let service = StubService::new(Some(UpstreamErrorKind::NotFound));
+        let err = persisted(service.clone(), store.clone()).call(serde()).wait().unwrap_err();
+        assert_eq!(UpstreamError::classify(&err), UpstreamErrorKind::NotFound);
+        assert_eq!(load(&store), None);
+
+        seed(&store, unix_now() - 1, 7);
+        let err = persisted(service.clone(), store.clone()).call(serde()).wait().unwrap_err();
+        assert_eq!(UpstreamError::classify(&err), UpstreamErrorKind::NotFound);
+        assert_eq!(load(&store), Some(7));
+        assert_eq!(service.calls(), 2);
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use futures::{Future, future};
+use futures::sync::oneshot;
+use tokio_core::reactor::{Handle, Timeout};
+use tokio_service::Service;
+
+/// Spaces out calls to a service by at least `interval`, to stay within
+/// the rate limits of an upstream API.
+///
+/// Calls are queued in the order they are made and run on the event loop,
+/// so a queued call still completes if its caller stops waiting for it.
+#[derive(Debug)]
+pub struct Throttle<S> {
+    inner: S,
+    handle: Handle,
+    interval: Duration,
+    next_slot: Arc<Mutex<Instant>>
+}
+
+impl<S> Throttle<S> {
+    pub fn new(service: S, handle: Handle, interval: Duration) -> Throttle<S> {
+        Throttle {
+            inner: service,
+            handle,
+            interval,
+            next_slot: Arc::new(Mutex::new(Instant::now()))
+        }
+    }
+}
+
+impl<S> Service for Throttle<S>
+    where S: Service<Error=Error> + Clone + 'static,
+          S::Request: 'static,
+          S::Response: 'static,
+          S::Future: 'static
+{
+    type Request = S::Request;
+    type Response = S::Response;
+    type Error = Error;
+    type Future = Box<Future<Item=Self::Response, Error=Self::Error>>;
+
+    fn call(&self, req: Self::Request) -> Self::Future {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().expect("lock poisoned");
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+
+        let timeout = try_future_box!(Timeout::new_at(slot, &self.handle));
+        let inner = self.inner.clone();
+        let (sender, receiver) = oneshot::channel();
+
+        self.handle.spawn(timeout.from_err().and_then(move |()| inner.call(req)).then(move |result| {
+            // the caller may have stopped waiting, which is fine
+            let _ = sender.send(result);
+            Ok(())
+        }));
+
+        Box::new(receiver
+            .map_err(|_| format_err!("throttled call was dropped"))
+            .and_then(future::result))
+    }
+}